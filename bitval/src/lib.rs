@@ -1,13 +1,168 @@
+//! Runtime support for the `rbitpack` derives.
+//!
+//! The crate is `no_std`. `Bitfield`, and with it the `size = "auto"` mode of the derive, needs the
+//! `alloc` feature, enabled by default. The `serde` feature is needed by structs deriving
+//! `Serialize`/`Deserialize` through `#[rbitpack(serde = "...")]`.
+
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+
+/// Items used by the code generated by the `rbitpack` derives. Not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(feature = "alloc")]
+    pub use alloc::vec;
+    #[cfg(feature = "alloc")]
+    pub use alloc::vec::Vec;
+    #[cfg(feature = "serde")]
+    pub use serde;
+
+    use core::fmt;
+
+    /// Formats a packed value as the struct it describes, for the generated `debug_packed`.
+    pub struct DebugPacked<I, const N: usize> {
+        pub struct_name: &'static str,
+        /// The name of each packed field, whether it is a `bool`, and the bits it holds.
+        pub fields: [(&'static str, bool, u128); N],
+        /// The indices of the bits set outside every field.
+        pub stray_bits: I,
+    }
+
+    impl<I, const N: usize> fmt::Debug for DebugPacked<I, N>
+    where
+        I: Iterator<Item = usize> + Clone,
+    {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let mut debug = f.debug_struct(self.struct_name);
+            // Only the fields that are set are listed, as they would be written in a literal.
+            for (name, is_bool, value) in &self.fields {
+                match (*is_bool, *value) {
+                    (_, 0) => {}
+                    (true, _) => {
+                        debug.field(name, &true);
+                    }
+                    (false, value) => {
+                        debug.field(name, &value);
+                    }
+                }
+            }
+            if self.stray_bits.clone().next().is_some() {
+                debug.field("stray_bits", &BitList(self.stray_bits.clone()));
+            }
+            debug.finish()
+        }
+    }
+
+    /// The packed type of a struct nested in another, whose bits are moved as a `u64`.
+    pub trait NestedPacked {
+        /// Whether the type is a fixed-size integer; only those can be nested.
+        const FIXED: bool;
+
+        fn to_bits(self) -> u64;
+
+        fn from_bits(bits: u64) -> Self;
+    }
+
+    macro_rules! nested_packed {
+        ($($ty:ty),*) => {$(
+            impl NestedPacked for $ty {
+                const FIXED: bool = true;
+
+                fn to_bits(self) -> u64 {
+                    self as u64
+                }
+
+                fn from_bits(bits: u64) -> Self {
+                    bits as $ty
+                }
+            }
+        )*};
+    }
+
+    nested_packed!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+    // Structs with `size = "auto"` cannot be nested; the derive rejects them at compile time
+    // through `FIXED` before these are ever called.
+    #[cfg(feature = "alloc")]
+    impl NestedPacked for Vec<u64> {
+        const FIXED: bool = false;
+
+        fn to_bits(self) -> u64 {
+            unreachable!("structs with `size = \"auto\"` cannot be nested")
+        }
+
+        fn from_bits(_bits: u64) -> Self {
+            unreachable!("structs with `size = \"auto\"` cannot be nested")
+        }
+    }
+
+    /// Formats the bit indices yielded by an iterator as a list.
+    struct BitList<I>(I);
+
+    impl<I> fmt::Debug for BitList<I>
+    where
+        I: Iterator<Item = usize> + Clone,
+    {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_list().entries(self.0.clone()).finish()
+        }
+    }
+}
+
+/// The order in which the bits of a `Bitfield` are numbered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BitOrder {
+    /// Bit 0 is the least significant bit of the first part.
+    #[default]
+    Lsb0,
+    /// Bit 0 is the most significant bit of the first part.
+    Msb0,
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Bitfield {
     pub parts: Vec<u64>, // Holds the bit values
+    order: BitOrder,     // How bit indices map to the bits of each part
 }
 
+#[cfg(feature = "alloc")]
 impl Bitfield {
-    /// Creates a new `Bitfield` with the specified number of bits.
+    /// Creates a new `Bitfield` with the specified number of bits, numbered from the least
+    /// significant bit.
     pub fn new(size: usize) -> Self {
-        let num_parts = (size + 63) / 64; // Calculate the number of `u64` parts needed
+        Bitfield::with_order(size, BitOrder::Lsb0)
+    }
+
+    /// Creates a new `Bitfield` with the specified number of bits, numbered in the given order.
+    pub fn with_order(size: usize, order: BitOrder) -> Self {
+        let num_parts = size.div_ceil(64); // Calculate the number of `u64` parts needed
         Bitfield {
             parts: vec![0; num_parts],
+            order,
+        }
+    }
+
+    /// Creates a `Bitfield` holding the given parts, with bits numbered in the given order.
+    pub fn from_parts(parts: Vec<u64>, order: BitOrder) -> Self {
+        Bitfield { parts, order }
+    }
+
+    /// Returns the order in which the bits are numbered.
+    pub fn order(&self) -> BitOrder {
+        self.order
+    }
+
+    /// Returns the position, within its part, of the bit at `index`.
+    fn bit_in_part(&self, index: usize) -> usize {
+        match self.order {
+            BitOrder::Lsb0 => index % 64,
+            BitOrder::Msb0 => 63 - index % 64,
         }
     }
 
@@ -17,7 +172,7 @@ impl Bitfield {
             panic!("Index out of bounds: {}", index);
         }
         let part = index / 64;
-        let bit = index % 64;
+        let bit = self.bit_in_part(index);
         if value {
             self.parts[part] |= 1 << bit;
         } else {
@@ -31,7 +186,202 @@ impl Bitfield {
             panic!("Index out of bounds: {}", index);
         }
         let part = index / 64;
-        let bit = index % 64;
+        let bit = self.bit_in_part(index);
         (self.parts[part] & (1 << bit)) != 0
     }
+
+    /// Returns which bit of a `width`-bit value is stored at `index + bit`: the low bits come
+    /// first in `Lsb0` order, and the high bits in `Msb0` order.
+    fn value_bit(&self, bit: usize, width: usize) -> usize {
+        match self.order {
+            BitOrder::Lsb0 => bit,
+            BitOrder::Msb0 => width - 1 - bit,
+        }
+    }
+
+    /// Sets `width` consecutive bits, starting at `index`, to the low bits of `value`.
+    /// In `Msb0` order, the most significant of those bits is stored at `index`.
+    pub fn set_bits(&mut self, index: usize, width: usize, value: u64) {
+        if width > 64 {
+            panic!("Width out of bounds: {}", width);
+        }
+        for bit in 0..width {
+            let value_bit = self.value_bit(bit, width);
+            self.set(index + bit, (value >> value_bit) & 1 != 0);
+        }
+    }
+
+    /// Gets `width` consecutive bits, starting at `index`, as an unsigned integer.
+    /// In `Msb0` order, the bit at `index` is the most significant bit of the result.
+    pub fn get_bits(&self, index: usize, width: usize) -> u64 {
+        if width > 64 {
+            panic!("Width out of bounds: {}", width);
+        }
+        let mut value = 0;
+        for bit in 0..width {
+            if self.get(index + bit) {
+                value |= 1 << self.value_bit(bit, width);
+            }
+        }
+        value
+    }
+
+    /// Returns the index of the first set bit at or after `index`, if any.
+    pub fn first_set_from(&self, index: usize) -> Option<usize> {
+        let start = index / 64;
+        self.parts
+            .iter()
+            .enumerate()
+            .skip(start)
+            .find_map(|(part, &bits)| {
+                // Ignore the bits before `index` in the first part
+                let skipped = if part == start { index % 64 } else { 0 };
+                let (bits, first) = match self.order {
+                    BitOrder::Lsb0 => {
+                        let bits = bits & (u64::MAX << skipped);
+                        (bits, bits.trailing_zeros())
+                    }
+                    BitOrder::Msb0 => {
+                        let bits = bits & (u64::MAX >> skipped);
+                        (bits, bits.leading_zeros())
+                    }
+                };
+                (bits != 0).then_some(part * 64 + first as usize)
+            })
+    }
+}
+
+/// An error returned by `try_unpack` when a packed value does not describe a valid struct.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnpackError {
+    /// The packed vector holds fewer bits than the fields of the struct need.
+    TooShort {
+        /// The name of the struct being unpacked.
+        struct_name: &'static str,
+        /// The number of bits the struct needs.
+        bits: usize,
+        /// The number of bits held by the packed vector.
+        available: usize,
+    },
+    /// A bit outside the fields of the struct is set: past the last field or, for
+    /// `unpack_strict`, between two fields.
+    UnexpectedBit {
+        /// The name of the struct being unpacked.
+        struct_name: &'static str,
+        /// The index of the first unexpected set bit.
+        bit: usize,
+    },
+    /// The packed vector does not hold exactly the number of parts the struct packs into, as
+    /// checked by `unpack_strict`.
+    LengthMismatch {
+        /// The name of the struct being unpacked.
+        struct_name: &'static str,
+        /// The number of parts the struct packs into.
+        expected: usize,
+        /// The number of parts held by the packed vector.
+        found: usize,
+    },
+    /// The bits of a field do not map to a value of its type, such as an enum discriminant
+    /// without a variant, or reserved bits that must be clear.
+    InvalidValue {
+        /// The name of the struct being unpacked.
+        struct_name: &'static str,
+        /// The name of the field.
+        field: &'static str,
+        /// The index of the first bit of the field.
+        bit: usize,
+        /// The bits read for the field.
+        value: u64,
+    },
+}
+
+impl core::fmt::Display for UnpackError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            UnpackError::TooShort {
+                struct_name,
+                bits,
+                available,
+            } => write!(
+                f,
+                "struct '{}' needs {} bits, but only {} were provided",
+                struct_name, bits, available
+            ),
+            UnpackError::UnexpectedBit { struct_name, bit } => write!(
+                f,
+                "bit {} is set outside the fields of struct '{}'",
+                bit, struct_name
+            ),
+            UnpackError::LengthMismatch {
+                struct_name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "struct '{}' packs into {} parts, but {} were provided",
+                struct_name, expected, found
+            ),
+            UnpackError::InvalidValue {
+                struct_name,
+                field,
+                bit,
+                value,
+            } => write!(
+                f,
+                "invalid value {} for field '{}' of struct '{}' at bit {}",
+                value, field, struct_name, bit
+            ),
+        }
+    }
+}
+
+impl core::error::Error for UnpackError {}
+
+/// A type whose fields can be packed into, and unpacked from, a compact bit representation.
+///
+/// This trait is implemented by `#[derive(BitwisePackable)]` from the `rbitpack` crate for
+/// every `size` mode, so generic code can accept any packable type through `T: BitPack`.
+pub trait BitPack: Sized {
+    /// The type the fields are packed into (`u8`, `u16`, `u32`, `u64`, `u128` or `Vec<u64>`).
+    type Packed;
+
+    /// The number of bits occupied by the packed fields.
+    const BITS: usize;
+
+    /// Packs the fields of `self` into its bit representation.
+    fn pack(&self) -> Self::Packed;
+
+    /// Builds a new value from its bit representation.
+    fn unpack(packed: Self::Packed) -> Self;
+
+    /// Builds a new value from its bit representation, returning an error instead of panicking
+    /// when the bits do not describe a valid value.
+    ///
+    /// The default implementation calls `unpack`; the derive overrides it to validate the input.
+    fn try_unpack(packed: Self::Packed) -> Result<Self, UnpackError> {
+        Ok(Self::unpack(packed))
+    }
+
+    /// Builds a new value from its bit representation like `try_unpack`, but also rejects bits
+    /// that belong to no field, and packed vectors whose length differs from the packed one.
+    ///
+    /// The default implementation calls `try_unpack`; the derive overrides it.
+    fn unpack_strict(packed: Self::Packed) -> Result<Self, UnpackError> {
+        Self::try_unpack(packed)
+    }
+}
+
+/// A fieldless enum that can be stored in a packed struct as its discriminant.
+///
+/// This trait is implemented by `#[derive(BitEnum)]` from the `rbitpack` crate, and used by
+/// `#[derive(BitwisePackable)]` for fields marked with `#[rbitpack(enum)]`.
+pub trait BitEnum: Sized {
+    /// The number of bits needed to store the largest discriminant.
+    const BITS: usize;
+
+    /// Returns the discriminant of the variant.
+    fn to_bits(&self) -> u64;
+
+    /// Returns the variant with the given discriminant, or `None` if no variant maps to it.
+    fn from_bits(bits: u64) -> Option<Self>;
 }
@@ -1,9 +1,14 @@
+#![allow(clippy::bool_assert_comparison)]
+
 extern crate bitval;
 
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "alloc")]
     use bitval::Bitfield;
+    use bitval::{BitOrder, UnpackError};
 
+    #[cfg(feature = "alloc")]
     #[test]
     fn test_new_bitfield() {
         let bitfield = Bitfield::new(128);
@@ -12,6 +17,7 @@ mod tests {
         assert_eq!(bitfield.parts[1], 0);
     }
 
+    #[cfg(feature = "alloc")]
     #[test]
     #[should_panic(expected = "Index out of bounds: 64")]
     fn test_set_out_of_bounds() {
@@ -19,6 +25,7 @@ mod tests {
         bitfield.set(64, true); // Out of bounds
     }
 
+    #[cfg(feature = "alloc")]
     #[test]
     #[should_panic(expected = "Index out of bounds: 64")]
     fn test_get_out_of_bounds() {
@@ -26,6 +33,7 @@ mod tests {
         bitfield.get(64); // Out of bounds
     }
 
+    #[cfg(feature = "alloc")]
     #[test]
     fn test_set_and_get() {
         let mut bitfield = Bitfield::new(128);
@@ -49,6 +57,7 @@ mod tests {
         assert_eq!(bitfield.get(126), false);
     }
 
+    #[cfg(feature = "alloc")]
     #[test]
     fn test_set_and_get_multiple_parts() {
         let mut bitfield = Bitfield::new(130); // More than one u64 part
@@ -67,6 +76,7 @@ mod tests {
         assert_eq!(bitfield.get(65), false);
     }
 
+    #[cfg(feature = "alloc")]
     #[test]
     fn test_set_bits() {
         let mut bitfield = Bitfield::new(64);
@@ -78,6 +88,7 @@ mod tests {
         assert_eq!(bitfield.parts[0], (1 << 3) | (1 << 7) | (1 << 63));
     }
 
+    #[cfg(feature = "alloc")]
     #[test]
     fn test_get_bits() {
         let mut bitfield = Bitfield::new(64);
@@ -89,4 +100,107 @@ mod tests {
         assert_eq!(bitfield.get(11), true);
         assert_eq!(bitfield.get(12), false);
     }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_set_and_get_multi_bit_values() {
+        let mut bitfield = Bitfield::new(128);
+
+        bitfield.set_bits(60, 8, 0b1010_0110);
+        bitfield.set_bits(0, 3, 0b1111); // Only the low 3 bits are stored
+
+        assert_eq!(bitfield.get_bits(60, 8), 0b1010_0110);
+        assert_eq!(bitfield.get_bits(0, 3), 0b111);
+        assert_eq!(bitfield.get(3), false);
+        assert_eq!(bitfield.parts[1], 0b1010);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    #[should_panic(expected = "Width out of bounds: 65")]
+    fn test_get_bits_width_out_of_bounds() {
+        let bitfield = Bitfield::new(128);
+        bitfield.get_bits(0, 65);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_first_set_from() {
+        let mut bitfield = Bitfield::new(192);
+        assert_eq!(bitfield.first_set_from(0), None);
+
+        bitfield.set(3, true);
+        bitfield.set(130, true);
+        assert_eq!(bitfield.first_set_from(0), Some(3));
+        assert_eq!(bitfield.first_set_from(3), Some(3));
+        assert_eq!(bitfield.first_set_from(4), Some(130));
+        assert_eq!(bitfield.first_set_from(131), None);
+        assert_eq!(bitfield.first_set_from(500), None);
+    }
+
+    #[test]
+    fn test_bit_order_default() {
+        assert_eq!(BitOrder::default(), BitOrder::Lsb0);
+    }
+
+    #[test]
+    fn test_unpack_error_display() {
+        let error = UnpackError::InvalidValue {
+            struct_name: "Status",
+            field: "mode",
+            bit: 1,
+            value: 3,
+        };
+        assert_eq!(
+            error.to_string(),
+            "invalid value 3 for field 'mode' of struct 'Status' at bit 1"
+        );
+
+        let error = UnpackError::LengthMismatch {
+            struct_name: "Wide",
+            expected: 2,
+            found: 3,
+        };
+        assert_eq!(
+            error.to_string(),
+            "struct 'Wide' packs into 2 parts, but 3 were provided"
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_set_and_get_msb0() {
+        let mut bitfield = Bitfield::with_order(128, BitOrder::Msb0);
+
+        bitfield.set(0, true);
+        bitfield.set(65, true);
+        assert_eq!(bitfield.parts, vec![1 << 63, 1 << 62]);
+        assert_eq!(bitfield.get(0), true);
+        assert_eq!(bitfield.get(63), false);
+        assert_eq!(bitfield.get(65), true);
+
+        // The most significant bit of the value is stored first.
+        bitfield.set_bits(4, 4, 0b1100);
+        assert_eq!(bitfield.parts[0], 0b1000_1100 << 56);
+        assert_eq!(bitfield.get_bits(4, 4), 0b1100);
+        assert_eq!(bitfield.first_set_from(1), Some(4));
+        assert_eq!(bitfield.first_set_from(6), Some(65));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_bit_orders_round_trip() {
+        for order in [BitOrder::Lsb0, BitOrder::Msb0] {
+            let mut bitfield = Bitfield::with_order(192, order);
+            bitfield.set_bits(0, 3, 0b101);
+            bitfield.set_bits(60, 10, 0b11_0110_1001);
+            bitfield.set(150, true);
+
+            assert_eq!(bitfield.get_bits(0, 3), 0b101);
+            assert_eq!(bitfield.get_bits(60, 10), 0b11_0110_1001);
+            assert_eq!(bitfield.get(150), true);
+            assert_eq!(bitfield.first_set_from(70), Some(150));
+        }
+        assert_eq!(Bitfield::new(8).order(), BitOrder::Lsb0);
+    }
 }
@@ -0,0 +1,29 @@
+//! Facade crate re-exporting the `rbitpack` derives together with the `bitval` items they use,
+//! so a single dependency is enough to pack structs.
+//!
+//! The derives find this crate among the dependencies, so the generated code reaches the `bitval`
+//! items through it without any extra attribute:
+//!
+//! ```rust
+//! use bitwise_packable::{BitPack, BitwisePackable};
+//!
+//! #[derive(BitwisePackable)]
+//! #[rbitpack(size = "u8")]
+//! struct Flags {
+//!     a: bool,
+//!     b: bool,
+//! }
+//!
+//! assert_eq!(BitPack::pack(&Flags { a: false, b: true }), 0b10);
+//! ```
+//!
+//! The crate is `no_std`. Disabling the default `alloc` feature leaves out `Bitfield` and the
+//! `size = "auto"` mode.
+
+#![no_std]
+
+pub use bitval::*;
+pub use rbitpack::*;
+
+/// The `bitval` crate, for code that refers to it by name.
+pub use bitval;
@@ -0,0 +1,92 @@
+#![allow(clippy::bool_assert_comparison)]
+
+#[cfg(test)]
+mod tests {
+    mod packed {
+        pub use bitwise_packable::*;
+    }
+
+    use bitwise_packable::{BitEnum, BitFlags, BitPack, BitwisePackable};
+
+    #[derive(BitEnum, Debug, PartialEq)]
+    #[rbitpack(crate = "bitwise_packable")]
+    enum Mode {
+        Idle,
+        Active,
+    }
+
+    #[test]
+    fn test_derive_through_facade() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "i8", crate = "bitwise_packable")]
+        struct Example {
+            a: bool,
+            #[rbitpack(enum)]
+            mode: Mode,
+        }
+
+        let packed = Example::pack(&Example {
+            a: true,
+            mode: Mode::Active,
+        });
+        assert_eq!(packed, 0b11);
+        let unpacked = Example::unpack(packed);
+        assert_eq!(unpacked.a, true);
+        assert_eq!(unpacked.mode, Mode::Active);
+    }
+
+    #[derive(BitEnum, Debug, PartialEq)]
+    enum Level {
+        Low,
+        High,
+    }
+
+    #[derive(BitFlags, Clone, Copy)]
+    enum Permission {
+        Read,
+        Write,
+    }
+
+    #[test]
+    fn test_derive_through_facade_by_default() {
+        // Without a `crate` attribute, the derives find the facade among the dependencies.
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8")]
+        struct Example {
+            a: bool,
+            #[rbitpack(enum)]
+            level: Level,
+        }
+
+        let packed = Example::pack(&Example {
+            a: false,
+            level: Level::High,
+        });
+        assert_eq!(packed, 0b10);
+        let unpacked = Example::unpack(packed);
+        assert_eq!(unpacked.a, false);
+        assert_eq!(unpacked.level, Level::High);
+
+        let permissions: PermissionSet =
+            [Permission::Read, Permission::Write].into_iter().collect();
+        assert_eq!(permissions.bits(), 0b11);
+    }
+
+    #[test]
+    fn test_crate_path_override() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto", crate = "self::packed")]
+        struct Example {
+            a: bool,
+            b: bool,
+        }
+
+        fn round_trip<T: BitPack>(value: &T) -> T {
+            T::unpack(value.pack())
+        }
+
+        let unpacked = round_trip(&Example { a: false, b: true });
+        assert_eq!(unpacked.a, false);
+        assert_eq!(unpacked.b, true);
+    }
+}
@@ -0,0 +1,262 @@
+use proc_macro2::Span;
+use proc_macro_crate::{crate_name, FoundCrate};
+use quote::ToTokens;
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Expr, ExprLit, Ident, Lit, LitStr, Path, Token};
+
+/// A single `key` or `key = value` entry of a `#[rbitpack(...)]` attribute. Values are parsed as
+/// expressions, so ranges such as `bits = 4..8` are accepted next to literals.
+struct AttributeArg {
+    key: Ident,
+    value: Option<Expr>,
+}
+
+impl Parse for AttributeArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // Keys may be keywords, as in `crate = "..."` or `enum`
+        let key = Ident::parse_any(input)?;
+        let value = if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        Ok(AttributeArg { key, value })
+    }
+}
+
+/// The keys of `#[rbitpack(...)]` on a struct deriving `BitwisePackable`, besides the `strict` flag.
+pub(crate) const STRUCT_KEYS: &[&str] = &[
+    "size",
+    "overflow",
+    "out_of_range",
+    "reserved",
+    "crate",
+    "order",
+    "endian",
+    "packed_type",
+    "serde",
+];
+
+/// The flags of `#[rbitpack(...)]` on a field of a struct deriving `BitwisePackable`.
+pub(crate) const FIELD_FLAGS: &[&str] = &["enum", "nested", "skip"];
+
+/// The keys of `#[rbitpack(...)]` on a field of a struct deriving `BitwisePackable`.
+pub(crate) const FIELD_KEYS: &[&str] = &["bits", "bit", "out_of_range", "reserved", "default"];
+
+/// The keys of `#[rbitpack(...)]` on an enum. They are shared by `BitEnum` and `BitFlags`, which
+/// may be derived for the same enum.
+pub(crate) const ENUM_KEYS: &[&str] = &["crate", "fallback", "set"];
+
+/// Returns the entries of every `#[rbitpack(...)]` attribute, or the error of the first one that
+/// does not parse.
+fn attribute_args(attrs: &[Attribute]) -> syn::Result<Vec<AttributeArg>> {
+    let mut args = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("rbitpack")) {
+        args.extend(attr.parse_args_with(Punctuated::<AttributeArg, Token![,]>::parse_terminated)?);
+    }
+    Ok(args)
+}
+
+/// Checks the `#[rbitpack(...)]` attributes of an item: every attribute must parse, and every
+/// entry must be one of the bare `flags`, or one of the `keys` given a value.
+pub(crate) fn check_attributes(
+    attrs: &[Attribute],
+    flags: &[&str],
+    keys: &[&str],
+) -> syn::Result<()> {
+    for arg in attribute_args(attrs)? {
+        let key = arg.key.to_string();
+        let (is_flag, is_key) = (flags.contains(&key.as_str()), keys.contains(&key.as_str()));
+        let error = match &arg.value {
+            None if is_flag => continue,
+            Some(_) if is_key => continue,
+            Some(value) if is_flag => {
+                syn::Error::new_spanned(value, format!("`{}` does not take a value", key))
+            }
+            None if is_key => syn::Error::new_spanned(
+                &arg.key,
+                format!("`{}` needs a value, as in `{} = ...`", key, key),
+            ),
+            _ => {
+                let expected: Vec<_> = flags
+                    .iter()
+                    .chain(keys)
+                    .map(|key| format!("`{}`", key))
+                    .collect();
+                syn::Error::new_spanned(
+                    &arg.key,
+                    format!(
+                        "unknown `rbitpack` attribute `{}`; expected one of {}",
+                        key,
+                        expected.join(", ")
+                    ),
+                )
+            }
+        };
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// Returns the value given to `key`, parsed as `T`, if the key is present.
+pub(crate) fn get_attribute_value<T>(attrs: &[Attribute], key: &str) -> syn::Result<Option<T>>
+where
+    T: syn::parse::Parse,
+{
+    // Find the specified key-value pair
+    let Some(value) = attribute_args(attrs)?
+        .into_iter()
+        .filter(|arg| arg.key == key)
+        .find_map(|arg| arg.value)
+    else {
+        return Ok(None);
+    };
+    syn::parse2(value.to_token_stream())
+        .map(Some)
+        .map_err(|error| {
+            syn::Error::new_spanned(&value, format!("invalid value for `{}`: {}", key, error))
+        })
+}
+
+/// Returns the string given to `key`, if the key is present, rejecting values that are not
+/// string literals.
+pub(crate) fn get_string_value(attrs: &[Attribute], key: &str) -> syn::Result<Option<LitStr>> {
+    match get_attribute_value::<Expr>(attrs, key)? {
+        None => Ok(None),
+        Some(Expr::Lit(ExprLit {
+            lit: Lit::Str(lit_str),
+            ..
+        })) => Ok(Some(lit_str)),
+        Some(value) => Err(syn::Error::new_spanned(
+            value,
+            format!(
+                "`{}` must be a string, in quotes, as in `{} = \"...\"`",
+                key, key
+            ),
+        )),
+    }
+}
+
+/// Returns whether a bare `key` flag, such as `#[rbitpack(enum)]`, is present.
+pub(crate) fn has_attribute_flag(attrs: &[Attribute], key: &str) -> syn::Result<bool> {
+    Ok(attribute_args(attrs)?
+        .iter()
+        .any(|arg| arg.key == key && arg.value.is_none()))
+}
+
+/// Returns the bits of the integer chosen by `#[rbitpack(size = "...")]` and whether it is signed,
+/// or `None` for `"auto"`, the default.
+pub(crate) fn get_packing_size(attrs: &[Attribute]) -> syn::Result<Option<(usize, bool)>> {
+    let Some(lit_str) = get_string_value(attrs, "size")? else {
+        return Ok(None);
+    };
+    let size = match lit_str.value().as_str() {
+        "auto" => return Ok(None),
+        "u8" => Some((8, false)),
+        "u16" => Some((16, false)),
+        "u32" => Some((32, false)),
+        "u64" => Some((64, false)),
+        "u128" => Some((128, false)),
+        "i8" => Some((8, true)),
+        "i16" => Some((16, true)),
+        "i32" => Some((32, true)),
+        "i64" => Some((64, true)),
+        "i128" => Some((128, true)),
+        _ => None,
+    };
+    size.map(Some).ok_or_else(|| {
+        syn::Error::new_spanned(
+            lit_str,
+            "`size` must be an integer type from \"u8\" to \"u128\" or from \"i8\" to \"i128\", or \"auto\"",
+        )
+    })
+}
+
+pub(crate) fn get_overflow_type(attrs: &[Attribute]) -> syn::Result<bool> {
+    match get_attribute_value::<Expr>(attrs, "overflow")? {
+        None => Ok(false),
+        Some(Expr::Lit(ExprLit {
+            lit: Lit::Bool(lit_bool),
+            ..
+        })) => Ok(lit_bool.value),
+        Some(value) => Err(syn::Error::new_spanned(
+            value,
+            "`overflow` must be `true` or `false`",
+        )),
+    }
+}
+
+/// Returns the path the generated code uses to reach the items of `bitval`: the value of
+/// `#[rbitpack(crate = "...")]`, or the default given by `default_crate_path`.
+pub(crate) fn get_crate_path(attrs: &[Attribute]) -> syn::Result<Path> {
+    match get_string_value(attrs, "crate")? {
+        Some(lit_str) => lit_str.parse(),
+        None => Ok(default_crate_path()),
+    }
+}
+
+/// Looks up the dependencies of the crate being compiled for the `bitwise_packable` facade, then
+/// for `bitval`, under the name the crate gives them, and falls back to `::bitval`.
+fn default_crate_path() -> Path {
+    for name in ["bitwise_packable", "bitval"] {
+        let found = match crate_name(name) {
+            Ok(FoundCrate::Name(found)) => found,
+            // Doctests and examples of the crate itself reach it by its name.
+            Ok(FoundCrate::Itself) => name.to_string(),
+            Err(_) => continue,
+        };
+        let ident = Ident::new(&found, Span::call_site());
+        return syn::parse_quote!(::#ident);
+    }
+    syn::parse_quote!(::bitval)
+}
+
+/// Returns the name given by `#[rbitpack(packed_type = "...")]`, if any.
+pub(crate) fn get_packed_type_name(attrs: &[Attribute]) -> syn::Result<Option<Ident>> {
+    get_string_value(attrs, "packed_type")?
+        .map(|lit_str| lit_str.parse())
+        .transpose()
+}
+
+/// The byte order of `pack_bytes`/`unpack_bytes`, from `#[rbitpack(endian = "...")]`.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Endian {
+    Little,
+    Big,
+}
+
+pub(crate) fn get_endian(attrs: &[Attribute]) -> syn::Result<Endian> {
+    match get_string_value(attrs, "endian")? {
+        None => Ok(Endian::Little),
+        Some(lit_str) if lit_str.value() == "little" => Ok(Endian::Little),
+        Some(lit_str) if lit_str.value() == "big" => Ok(Endian::Big),
+        Some(lit) => Err(syn::Error::new_spanned(
+            lit,
+            "`endian` must be one of \"little\" or \"big\"",
+        )),
+    }
+}
+
+/// How `#[rbitpack(serde = "...")]` serializes the struct.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum SerdeMode {
+    /// As its packed value.
+    Packed,
+    /// As the list of the names of its set `bool` fields.
+    Names,
+}
+
+pub(crate) fn get_serde_mode(attrs: &[Attribute]) -> syn::Result<Option<SerdeMode>> {
+    match get_string_value(attrs, "serde")? {
+        None => Ok(None),
+        Some(lit_str) if lit_str.value() == "packed" => Ok(Some(SerdeMode::Packed)),
+        Some(lit_str) if lit_str.value() == "names" => Ok(Some(SerdeMode::Names)),
+        Some(lit) => Err(syn::Error::new_spanned(
+            lit,
+            "`serde` must be one of \"packed\" or \"names\"",
+        )),
+    }
+}
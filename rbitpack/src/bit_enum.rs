@@ -0,0 +1,99 @@
+use crate::attrs::{check_attributes, get_crate_path, get_string_value, ENUM_KEYS};
+use proc_macro2::{Literal, TokenStream};
+use quote::quote;
+use syn::{Data, DeriveInput, Expr, ExprLit, Fields, Lit};
+
+/// Generates the `bitval::BitEnum` implementation for a fieldless enum.
+pub(crate) fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    check_attributes(&input.attrs, &[], ENUM_KEYS)?;
+    let krate = get_crate_path(&input.attrs)?;
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            name,
+            "BitEnum can only be used with enums",
+        ));
+    };
+
+    // Discriminants follow the same rules as Rust: explicit when given, otherwise one more than
+    // the previous variant.
+    let mut variants = Vec::new();
+    let mut next = 0u64;
+    for variant in data.variants.iter() {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "BitEnum can only be used with fieldless enums",
+            ));
+        }
+        let discriminant = match &variant.discriminant {
+            Some((
+                _,
+                Expr::Lit(ExprLit {
+                    lit: Lit::Int(lit_int),
+                    ..
+                }),
+            )) => lit_int.base10_parse::<u64>()?,
+            Some((_, expr)) => {
+                return Err(syn::Error::new_spanned(
+                    expr,
+                    "BitEnum discriminants must be non-negative integer literals",
+                ))
+            }
+            None => next,
+        };
+        next = discriminant.wrapping_add(1);
+        variants.push((&variant.ident, discriminant));
+    }
+
+    let max = variants.iter().map(|(_, d)| *d).max().unwrap_or(0);
+    let bits = ((64 - max.leading_zeros()) as usize).max(1);
+
+    let fallback = match get_string_value(&input.attrs, "fallback")? {
+        Some(lit_str) => {
+            let Some((ident, _)) = variants
+                .iter()
+                .find(|(ident, _)| **ident == lit_str.value())
+            else {
+                return Err(syn::Error::new_spanned(
+                    lit_str,
+                    format!("enum '{}' has no such variant", name),
+                ));
+            };
+            quote!(::core::option::Option::Some(Self::#ident))
+        }
+        None => quote!(::core::option::Option::None),
+    };
+
+    let idents = variants.iter().map(|(ident, _)| ident);
+    let discriminants = variants
+        .iter()
+        .map(|(_, d)| Literal::u64_unsuffixed(*d))
+        .collect::<Vec<_>>();
+    let to_bits_arms = idents
+        .clone()
+        .zip(&discriminants)
+        .map(|(ident, d)| quote!(Self::#ident => #d,));
+    let from_bits_arms = idents
+        .zip(&discriminants)
+        .map(|(ident, d)| quote!(#d => ::core::option::Option::Some(Self::#ident),));
+
+    Ok(quote! {
+        impl #krate::BitEnum for #name {
+            const BITS: usize = #bits;
+
+            fn to_bits(&self) -> u64 {
+                match *self {
+                    #(#to_bits_arms)*
+                }
+            }
+
+            fn from_bits(bits: u64) -> ::core::option::Option<Self> {
+                match bits {
+                    #(#from_bits_arms)*
+                    _ => #fallback,
+                }
+            }
+        }
+    })
+}
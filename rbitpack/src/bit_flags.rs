@@ -0,0 +1,383 @@
+use crate::attrs::{check_attributes, get_crate_path, get_string_value, ENUM_KEYS};
+use proc_macro2::{Literal, TokenStream};
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields, Ident};
+
+/// Generates the flag-set type for a fieldless enum: a set of its variants, each stored as one
+/// bit, in declaration order.
+pub(crate) fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let vis = &input.vis;
+    check_attributes(&input.attrs, &[], ENUM_KEYS)?;
+    let krate = get_crate_path(&input.attrs)?;
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            name,
+            "BitFlags can only be used with enums",
+        ));
+    };
+    if let Some(variant) = data
+        .variants
+        .iter()
+        .find(|variant| !matches!(variant.fields, Fields::Unit))
+    {
+        return Err(syn::Error::new_spanned(
+            variant,
+            "BitFlags can only be used with fieldless enums",
+        ));
+    }
+
+    let set = match get_string_value(&input.attrs, "set")? {
+        Some(lit_str) => lit_str.parse::<Ident>()?,
+        None => format_ident!("{}Set", name),
+    };
+
+    let count = data.variants.len();
+    let variants = data.variants.iter().map(|variant| &variant.ident);
+    let names = variants.clone().map(|variant| variant.to_string());
+    let bits = 0..count;
+    let flag_bit_arms = variants
+        .clone()
+        .zip(bits.clone())
+        .map(|(variant, bit)| quote!(#name::#variant => #bit,));
+    let flag_at_arms = variants
+        .zip(bits)
+        .map(|(variant, bit)| quote!(#bit => ::core::option::Option::Some(#name::#variant),));
+
+    // The set is backed by the smallest unsigned integer holding a bit per variant, or by a
+    // `Bitfield` past 64 variants.
+    let raw_ty = match count {
+        0..=8 => Some(quote!(u8)),
+        9..=16 => Some(quote!(u16)),
+        17..=32 => Some(quote!(u32)),
+        33..=64 => Some(quote!(u64)),
+        _ => None,
+    };
+    let backing = match &raw_ty {
+        Some(raw_ty) => {
+            let all = Literal::u128_unsuffixed((1u128 << count) - 1);
+            Backing {
+                derives: quote!(#[derive(Clone, Copy, PartialEq, Eq, Hash)]),
+                field_ty: raw_ty.clone(),
+                raw_ty: raw_ty.clone(),
+                empty: quote!(0),
+                all: quote!(#all),
+                has_bit: quote!(self.0 & (1 << bit) != 0),
+                set_bit: quote! {
+                    if value {
+                        self.0 |= 1 << bit;
+                    } else {
+                        self.0 &= !(1 << bit);
+                    }
+                },
+                is_empty: quote!(self.0 == 0),
+                len: quote!(self.0.count_ones() as usize),
+                union: quote!(#set(self.0 | other.0)),
+                intersection: quote!(#set(self.0 & other.0)),
+                difference: quote!(#set(self.0 & !other.0)),
+                bits: quote!(self.0),
+                from_bits: quote! {
+                    if bits & !#all == 0 {
+                        ::core::option::Option::Some(#set(bits))
+                    } else {
+                        ::core::option::Option::None
+                    }
+                },
+                from_bits_truncate: quote!(#set(bits & #all)),
+            }
+        }
+        None => {
+            let parts = count.div_ceil(64);
+            let all_parts = (0..parts).map(|part| {
+                let bits = (count - part * 64).min(64);
+                Literal::u64_unsuffixed(if bits == 64 {
+                    u64::MAX
+                } else {
+                    (1 << bits) - 1
+                })
+            });
+            let bitfield = |parts: TokenStream| quote!(#krate::Bitfield::from_parts(#parts, #krate::BitOrder::Lsb0));
+            let combine = |op: TokenStream| {
+                bitfield(quote! {
+                    self.0
+                        .parts
+                        .iter()
+                        .zip(&other.0.parts)
+                        .map(|(a, b)| #op)
+                        .collect()
+                })
+            };
+            let all = bitfield(quote!(#krate::__private::vec![#(#all_parts),*]));
+            let union = combine(quote!(a | b));
+            let intersection = combine(quote!(a & b));
+            let difference = combine(quote!(a & !b));
+            let truncated = bitfield(quote!(bits));
+            Backing {
+                derives: quote!(#[derive(Clone, PartialEq, Eq, Hash)]),
+                field_ty: quote!(#krate::Bitfield),
+                raw_ty: quote!(#krate::__private::Vec<u64>),
+                empty: quote!(#krate::Bitfield::new(#count)),
+                all: quote!(#all),
+                has_bit: quote!(self.0.get(bit)),
+                set_bit: quote!(self.0.set(bit, value);),
+                is_empty: quote!(self.0.parts.iter().all(|part| *part == 0)),
+                len: quote! {
+                    self.0
+                        .parts
+                        .iter()
+                        .map(|part| part.count_ones() as usize)
+                        .sum()
+                },
+                union: quote!(#set(#union)),
+                intersection: quote!(#set(#intersection)),
+                difference: quote!(#set(#difference)),
+                bits: quote!(self.0.parts.clone()),
+                from_bits: quote! {
+                    let set = Self::from_bits_truncate(bits.clone());
+                    if set.0.parts == bits {
+                        ::core::option::Option::Some(set)
+                    } else {
+                        ::core::option::Option::None
+                    }
+                },
+                from_bits_truncate: quote! {
+                    let mut bits = bits;
+                    bits.resize(#parts, 0);
+                    Self::all().intersection(&#set(#truncated))
+                },
+            }
+        }
+    };
+    let Backing {
+        derives,
+        field_ty,
+        raw_ty,
+        empty,
+        all,
+        has_bit,
+        set_bit,
+        is_empty,
+        len,
+        union,
+        intersection,
+        difference,
+        bits,
+        from_bits,
+        from_bits_truncate,
+    } = backing;
+
+    let doc = format!(
+        " A set of [`{}`] flags, each stored as one bit, in the order the variants are declared.",
+        name
+    );
+
+    Ok(quote! {
+        #[doc = #doc]
+        #derives
+        #vis struct #set(#field_ty);
+
+        impl #set {
+            /// Returns an empty set.
+            pub fn empty() -> Self {
+                #set(#empty)
+            }
+
+            /// Returns a set holding every flag.
+            pub fn all() -> Self {
+                #set(#all)
+            }
+
+            /// Returns the raw bits of the set, with the bit of each flag set.
+            pub fn bits(&self) -> #raw_ty {
+                #bits
+            }
+
+            /// Builds a set from raw bits, or returns `None` if a set bit does not belong to any flag.
+            pub fn from_bits(bits: #raw_ty) -> ::core::option::Option<Self> {
+                #from_bits
+            }
+
+            /// Builds a set from raw bits, dropping the bits that do not belong to any flag.
+            pub fn from_bits_truncate(bits: #raw_ty) -> Self {
+                #from_bits_truncate
+            }
+
+            /// Returns whether the set holds no flag.
+            pub fn is_empty(&self) -> bool {
+                #is_empty
+            }
+
+            /// Returns the number of flags in the set.
+            pub fn len(&self) -> usize {
+                #len
+            }
+
+            /// Returns whether the set holds `flag`.
+            pub fn contains(&self, flag: #name) -> bool {
+                self.has_bit(Self::flag_bit(&flag))
+            }
+
+            /// Adds `flag` to the set, and returns whether it was missing.
+            pub fn insert(&mut self, flag: #name) -> bool {
+                let bit = Self::flag_bit(&flag);
+                let missing = !self.has_bit(bit);
+                self.set_bit(bit, true);
+                missing
+            }
+
+            /// Removes `flag` from the set, and returns whether it was present.
+            pub fn remove(&mut self, flag: #name) -> bool {
+                let bit = Self::flag_bit(&flag);
+                let present = self.has_bit(bit);
+                self.set_bit(bit, false);
+                present
+            }
+
+            /// Returns the flags held by either set.
+            pub fn union(&self, other: &Self) -> Self {
+                #union
+            }
+
+            /// Returns the flags held by both sets.
+            pub fn intersection(&self, other: &Self) -> Self {
+                #intersection
+            }
+
+            /// Returns the flags held by this set but not by `other`.
+            pub fn difference(&self, other: &Self) -> Self {
+                #difference
+            }
+
+            /// Returns the flags in the set, in declaration order.
+            pub fn iter(&self) -> impl ::core::iter::Iterator<Item = #name> + '_ {
+                (0..#count)
+                    .filter(move |bit| self.has_bit(*bit))
+                    .filter_map(Self::flag_at)
+            }
+
+            fn flag_bit(flag: &#name) -> usize {
+                match *flag {
+                    #(#flag_bit_arms)*
+                }
+            }
+
+            fn flag_at(bit: usize) -> ::core::option::Option<#name> {
+                match bit {
+                    #(#flag_at_arms)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+
+            fn has_bit(&self, bit: usize) -> bool {
+                #has_bit
+            }
+
+            fn set_bit(&mut self, bit: usize, value: bool) {
+                #set_bit
+            }
+        }
+
+        impl ::core::fmt::Debug for #set {
+            /// Lists the names of the flags in the set, as in `PermissionSet {Read, Write}`.
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                /// Writes a flag name without quotes.
+                struct FlagName(&'static str);
+
+                impl ::core::fmt::Debug for FlagName {
+                    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                        f.write_str(self.0)
+                    }
+                }
+
+                const NAMES: [&str; #count] = [#(#names),*];
+                f.write_str(::core::concat!(::core::stringify!(#set), " "))?;
+                f.debug_set()
+                    .entries(
+                        (0..#count)
+                            .filter(|bit| self.has_bit(*bit))
+                            .map(|bit| FlagName(NAMES[bit])),
+                    )
+                    .finish()
+            }
+        }
+
+        impl ::core::default::Default for #set {
+            fn default() -> Self {
+                Self::empty()
+            }
+        }
+
+        impl ::core::convert::From<#name> for #set {
+            fn from(flag: #name) -> Self {
+                let mut set = Self::empty();
+                set.insert(flag);
+                set
+            }
+        }
+
+        impl ::core::convert::From<#set> for #raw_ty {
+            fn from(set: #set) -> Self {
+                set.bits()
+            }
+        }
+
+        impl ::core::iter::FromIterator<#name> for #set {
+            fn from_iter<I: ::core::iter::IntoIterator<Item = #name>>(flags: I) -> Self {
+                let mut set = Self::empty();
+                set.extend(flags);
+                set
+            }
+        }
+
+        impl ::core::iter::Extend<#name> for #set {
+            fn extend<I: ::core::iter::IntoIterator<Item = #name>>(&mut self, flags: I) {
+                for flag in flags {
+                    self.insert(flag);
+                }
+            }
+        }
+
+        impl ::core::ops::BitOr for #set {
+            type Output = Self;
+
+            fn bitor(self, other: Self) -> Self {
+                self.union(&other)
+            }
+        }
+
+        impl ::core::ops::BitAnd for #set {
+            type Output = Self;
+
+            fn bitand(self, other: Self) -> Self {
+                self.intersection(&other)
+            }
+        }
+
+        impl ::core::ops::Sub for #set {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                self.difference(&other)
+            }
+        }
+    })
+}
+
+/// The parts of the generated set that depend on how its bits are stored.
+struct Backing {
+    derives: TokenStream,
+    field_ty: TokenStream,
+    raw_ty: TokenStream,
+    empty: TokenStream,
+    all: TokenStream,
+    has_bit: TokenStream,
+    set_bit: TokenStream,
+    is_empty: TokenStream,
+    len: TokenStream,
+    union: TokenStream,
+    intersection: TokenStream,
+    difference: TokenStream,
+    bits: TokenStream,
+    from_bits: TokenStream,
+    from_bits_truncate: TokenStream,
+}
@@ -0,0 +1,96 @@
+use crate::layout::{BitOrder, Layout};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{DeriveInput, GenericParam, Lifetime, LifetimeDef, Path};
+
+/// Generates the standard conversions between the struct and its packed value `packed_ty`:
+/// `From` the struct, and from a reference to it, into the packed value and, in `auto` mode,
+/// into `bitval::Bitfield`; and `From` the packed value back into the struct through `unpack`.
+/// The conversion back is `TryFrom` instead when `unpack` could panic, through `try_unpack`, and
+/// with `#[rbitpack(strict)]`, through `unpack_strict`.
+pub(crate) fn expand(
+    input: &DeriveInput,
+    layout: &Layout,
+    krate: &Path,
+    packed_ty: &TokenStream,
+    auto: bool,
+    overflow: bool,
+    strict: bool,
+) -> TokenStream {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    // The conversions from a reference need a lifetime of their own.
+    let lifetime = Lifetime::new("'rbitpack", Span::call_site());
+    let mut ref_generics = input.generics.clone();
+    ref_generics.params.insert(
+        0,
+        GenericParam::Lifetime(LifetimeDef::new(lifetime.clone())),
+    );
+    let (ref_impl_generics, _, _) = ref_generics.split_for_impl();
+
+    // In `auto` mode, `unpack` also panics on vectors too short for the fields.
+    let fallible = layout.unpack_can_fail() || (auto && !overflow);
+    let from_packed = if strict || fallible {
+        let unpack = if strict {
+            quote!(unpack_strict)
+        } else {
+            quote!(try_unpack)
+        };
+        quote! {
+            impl #impl_generics ::core::convert::TryFrom<#packed_ty> for #name #ty_generics #where_clause {
+                type Error = #krate::UnpackError;
+
+                fn try_from(packed: #packed_ty) -> ::core::result::Result<Self, Self::Error> {
+                    #name::#unpack(packed)
+                }
+            }
+        }
+    } else {
+        quote! {
+            impl #impl_generics ::core::convert::From<#packed_ty> for #name #ty_generics #where_clause {
+                fn from(packed: #packed_ty) -> Self {
+                    #name::unpack(packed)
+                }
+            }
+        }
+    };
+
+    let into_bitfield = auto.then(|| {
+        let order = match layout.order {
+            BitOrder::Lsb0 => quote!(#krate::BitOrder::Lsb0),
+            BitOrder::Msb0 => quote!(#krate::BitOrder::Msb0),
+        };
+        quote! {
+            impl #impl_generics ::core::convert::From<#name #ty_generics> for #krate::Bitfield #where_clause {
+                fn from(value: #name #ty_generics) -> Self {
+                    #krate::Bitfield::from_parts(value.pack(), #order)
+                }
+            }
+
+            impl #ref_impl_generics ::core::convert::From<&#lifetime #name #ty_generics> for #krate::Bitfield #where_clause {
+                fn from(value: &#lifetime #name #ty_generics) -> Self {
+                    #krate::Bitfield::from_parts(value.pack(), #order)
+                }
+            }
+        }
+    });
+
+    quote! {
+        impl #impl_generics ::core::convert::From<#name #ty_generics> for #packed_ty #where_clause {
+            fn from(value: #name #ty_generics) -> Self {
+                value.pack()
+            }
+        }
+
+        impl #ref_impl_generics ::core::convert::From<&#lifetime #name #ty_generics> for #packed_ty #where_clause {
+            fn from(value: &#lifetime #name #ty_generics) -> Self {
+                value.pack()
+            }
+        }
+
+        #from_packed
+
+        #into_bitfield
+    }
+}
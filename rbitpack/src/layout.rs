@@ -0,0 +1,821 @@
+use crate::attrs::{
+    check_attributes, get_attribute_value, get_string_value, has_attribute_flag, FIELD_FLAGS,
+    FIELD_KEYS,
+};
+use proc_macro2::{Literal, TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
+use syn::{
+    Attribute, DataStruct, Expr, ExprLit, Field, Fields, Generics, Ident, Index, Lit, Member, Path,
+    RangeLimits, Type,
+};
+
+/// How `pack` handles an integer field whose value does not fit in its `bits`.
+#[derive(Clone, Copy)]
+pub(crate) enum RangePolicy {
+    /// Keep only the low `bits` bits of the value.
+    Truncate,
+    /// Clamp the value to the largest one that fits.
+    Saturate,
+    /// Panic with a message naming the struct and the field.
+    Error,
+}
+
+/// How the bits of the packed value are numbered, from `#[rbitpack(order = "...")]`.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum BitOrder {
+    /// Bit 0 is the least significant bit (the default).
+    Lsb0,
+    /// Bit 0 is the most significant bit.
+    Msb0,
+}
+
+/// What `unpack` does with reserved bits, from `#[rbitpack(reserved = "...")]` on the struct.
+#[derive(Clone, Copy)]
+pub(crate) enum ReservedPolicy {
+    /// Leave them out: `pack` clears them and `unpack` does not read them.
+    Ignore,
+    /// Require them to be clear: `unpack` panics, and `try_unpack` returns an error, when one is
+    /// set.
+    Zero,
+    /// Keep them in the reserved field, so they survive a round trip.
+    Preserve,
+}
+
+/// What a packed field holds, and therefore how it is converted to and from its bits.
+pub(crate) enum FieldKind<'a> {
+    /// A `bool`, stored in a single bit.
+    Bool,
+    /// An unsigned integer (`u8` to `u64`) stored in `bits` consecutive bits.
+    Int { ty: &'a Type, policy: RangePolicy },
+    /// A fieldless enum implementing `bitval::BitEnum`, stored as its discriminant.
+    Enum { ty: &'a Type },
+    /// A struct implementing `bitval::BitPack` with a fixed size, stored as its packed value.
+    Nested { ty: &'a Type },
+    /// Bits reserved with `#[rbitpack(reserved = N)]`, held by a field of the unsigned integer
+    /// type `ty`, or by a `()` field when `ty` is `None`.
+    Reserved {
+        ty: Option<&'a Type>,
+        policy: ReservedPolicy,
+    },
+}
+
+/// A number of bits: the part known while the macro runs, plus `BITS` constants of field types
+/// (such as `<Mode as ::bitval::BitEnum>::BITS`) that are only known to the compiler.
+#[derive(Clone, Default)]
+pub(crate) struct BitCount {
+    pub known: usize,
+    pub terms: Vec<TokenStream>,
+}
+
+impl BitCount {
+    pub fn from_known(known: usize) -> Self {
+        BitCount {
+            known,
+            terms: Vec::new(),
+        }
+    }
+
+    /// The number of bits, if it does not depend on any field type.
+    pub fn value(&self) -> Option<usize> {
+        if self.terms.is_empty() {
+            Some(self.known)
+        } else {
+            None
+        }
+    }
+
+    pub fn add(&mut self, other: &BitCount) {
+        self.known += other.known;
+        self.terms.extend(other.terms.iter().cloned());
+    }
+}
+
+impl ToTokens for BitCount {
+    /// Expands to a `usize` constant expression.
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let known = Literal::usize_unsuffixed(self.known);
+        if self.terms.is_empty() {
+            known.to_tokens(tokens);
+            return;
+        }
+
+        let terms = &self.terms;
+        tokens.extend(quote! {
+            (#known #(+ #terms)*)
+        });
+    }
+}
+
+/// A field of the struct together with the bits it occupies in the packed value.
+pub(crate) struct PackedField<'a> {
+    pub field: &'a Field,
+    /// How the field is reached from `self`: its name, or its index in a tuple struct.
+    pub member: Member,
+    /// Names the items generated for the field: the field name, or `field_0`, `field_1`, ... in a
+    /// tuple struct.
+    pub ident: Ident,
+    /// The local variable holding the field while unpacking, `__rbitpack_field_0`, ... so that it
+    /// cannot shadow the locals of the generated code, such as `packed` or `bitfield`.
+    pub local: Ident,
+    pub kind: FieldKind<'a>,
+    /// Index of the lowest bit of the field.
+    pub offset: BitCount,
+    /// Number of bits occupied by the field.
+    pub width: BitCount,
+    /// The `bit` or `bits` attribute pinning the field at a given position, if any.
+    pub pinned: Option<Expr>,
+}
+
+impl<'a> PackedField<'a> {
+    /// The name of the field as written in error messages, debug output and `serde = "names"`.
+    pub fn name(&self) -> String {
+        member_name(&self.member)
+    }
+
+    /// The largest value that fits in the bits of this field.
+    pub fn max_value(&self) -> u128 {
+        low_mask(self.width.known)
+    }
+
+    /// The bit just past the end of the field.
+    pub fn end(&self) -> BitCount {
+        let mut end = self.offset.clone();
+        end.add(&self.width);
+        end
+    }
+
+    /// Whether the field can be read in a `const fn`. Enum and nested fields go through trait
+    /// methods, which constant evaluation cannot call.
+    pub fn const_unpack(&self) -> bool {
+        matches!(
+            self.kind,
+            FieldKind::Bool | FieldKind::Int { .. } | FieldKind::Reserved { .. }
+        )
+    }
+
+    /// Whether the field can be written in a `const fn`. Besides enum and nested fields, this
+    /// rules out the `"error"` policy, whose panic message is formatted.
+    pub fn const_pack(&self) -> bool {
+        match self.kind {
+            FieldKind::Bool | FieldKind::Reserved { .. } => true,
+            FieldKind::Int { policy, .. } => !matches!(policy, RangePolicy::Error),
+            FieldKind::Enum { .. } | FieldKind::Nested { .. } => false,
+        }
+    }
+}
+
+/// Returns the mask of the `width` low bits of a `u128`, for any width up to 128.
+pub(crate) fn low_mask(width: usize) -> u128 {
+    1u128
+        .checked_shl(width as u32)
+        .map_or(u128::MAX, |bit| bit - 1)
+}
+
+/// Returns the name of a field as written in error messages and debug output: its name without
+/// any `r#` prefix, or its index in a tuple struct.
+fn member_name(member: &Member) -> String {
+    match member {
+        Member::Named(ident) => ident.unraw().to_string(),
+        Member::Unnamed(index) => index.index.to_string(),
+    }
+}
+
+/// A field of the struct that is not packed, and is filled with a default value by `unpack`.
+pub(crate) struct SkippedField {
+    pub member: Member,
+    /// Expression producing the value of the field.
+    pub default: TokenStream,
+    /// Whether `default` can be evaluated in a `const fn`, which only `PhantomData` is known to be.
+    pub constant: bool,
+}
+
+impl SkippedField {
+    fn new(member: Member, field: &Field) -> syn::Result<Self> {
+        Ok(SkippedField {
+            member,
+            default: default_value(field)?,
+            constant: is_phantom_data(&field.ty)
+                && get_string_value(&field.attrs, "default")?.is_none(),
+        })
+    }
+}
+
+/// The fields of a struct, split between the packed ones and the ones left out.
+pub(crate) struct Layout<'a> {
+    /// Whether the struct has named fields, positional fields, or none at all.
+    pub style: &'a Fields,
+    pub packed: Vec<PackedField<'a>>,
+    pub skipped: Vec<SkippedField>,
+    /// Assertions on the layout that only the compiler can evaluate, as constant expressions.
+    pub checks: Vec<TokenStream>,
+    pub order: BitOrder,
+}
+
+impl<'a> Layout<'a> {
+    /// Builds `Self` from the packed fields bound to their local variables, and the defaults of
+    /// the skipped fields.
+    pub fn constructor(&self) -> TokenStream {
+        match self.style {
+            Fields::Named(_) => {
+                let packed = self.packed.iter().map(|f| {
+                    let member = &f.member;
+                    let local = &f.local;
+                    quote!(#member: #local)
+                });
+                let skipped = self.skipped.iter().map(|f| {
+                    let member = &f.member;
+                    let default = &f.default;
+                    quote!(#member: #default)
+                });
+                quote! {
+                    Self {
+                        #(#packed,)*
+                        #(#skipped,)*
+                    }
+                }
+            }
+            Fields::Unnamed(_) => {
+                // Positional fields must be listed in declaration order.
+                let mut values: Vec<_> = self
+                    .packed
+                    .iter()
+                    .map(|f| (&f.member, f.local.to_token_stream()))
+                    .chain(self.skipped.iter().map(|f| (&f.member, f.default.clone())))
+                    .collect();
+                values.sort_by_key(|(member, _)| match member {
+                    Member::Unnamed(index) => index.index,
+                    Member::Named(_) => unreachable!("tuple structs only have positional fields"),
+                });
+                let values = values.into_iter().map(|(_, value)| value);
+                quote!(Self(#(#values,)*))
+            }
+            Fields::Unit => quote!(Self),
+        }
+    }
+
+    /// Whether `pack` can be a `const fn`.
+    pub fn const_pack(&self) -> bool {
+        self.packed.iter().all(PackedField::const_pack)
+    }
+
+    /// Whether `unpack` can be a `const fn`: besides reading every packed field, it must fill
+    /// the skipped ones.
+    pub fn const_unpack(&self) -> bool {
+        self.packed.iter().all(PackedField::const_unpack)
+            && self.skipped.iter().all(|field| field.constant)
+    }
+
+    /// Whether `unpack` may panic on some packed value of the fixed sizes: an enum discriminant
+    /// without a variant, a nested struct that fails to unpack, or reserved bits that must be
+    /// clear. Enums are assumed to be able to fail, since their `fallback` is not visible here.
+    pub fn unpack_can_fail(&self) -> bool {
+        self.packed.iter().any(|field| {
+            matches!(
+                field.kind,
+                FieldKind::Enum { .. }
+                    | FieldKind::Nested { .. }
+                    | FieldKind::Reserved {
+                        policy: ReservedPolicy::Zero,
+                        ..
+                    }
+            )
+        })
+    }
+
+    /// Returns whether the width of a packed field may depend on the type or const parameters
+    /// of the struct, which is then only known once the struct is instantiated.
+    pub fn depends_on(&self, generics: &Generics) -> bool {
+        let params: Vec<&Ident> = generics
+            .type_params()
+            .map(|param| &param.ident)
+            .chain(generics.const_params().map(|param| &param.ident))
+            .collect();
+        fn mentions(tokens: TokenStream, params: &[&Ident]) -> bool {
+            tokens.into_iter().any(|token| match token {
+                TokenTree::Ident(ident) => params.contains(&&ident),
+                TokenTree::Group(group) => mentions(group.stream(), params),
+                _ => false,
+            })
+        }
+        self.packed
+            .iter()
+            .any(|field| mentions(field.field.ty.to_token_stream(), &params))
+    }
+}
+
+/// Returns whether `ty` is `PhantomData`, such as the marker field of a generic struct.
+fn is_phantom_data(ty: &Type) -> bool {
+    matches!(ty, Type::Path(syn::TypePath { path, .. })
+        if path.segments.last().is_some_and(|segment| segment.ident == "PhantomData"))
+}
+
+/// Returns the expression filling a skipped field: a call to the function named by its
+/// `#[rbitpack(default = "path")]` attribute, `PhantomData` for `PhantomData` fields, or
+/// `Default::default()`.
+fn default_value(field: &Field) -> syn::Result<TokenStream> {
+    match get_string_value(&field.attrs, "default")? {
+        Some(lit_str) => {
+            let path = lit_str.parse::<syn::ExprPath>()?;
+            Ok(quote!(#path()))
+        }
+        None if is_phantom_data(&field.ty) => Ok(quote!(::core::marker::PhantomData)),
+        None => Ok(quote!(::core::default::Default::default())),
+    }
+}
+
+/// Returns the bit width of the unsigned integer types supported as multi-bit fields.
+fn int_width(ty: &Type) -> Option<usize> {
+    let Type::Path(syn::TypePath { path, .. }) = ty else {
+        return None;
+    };
+    let ident = path.get_ident()?;
+    match ident.to_string().as_str() {
+        "u8" => Some(8),
+        "u16" => Some(16),
+        "u32" => Some(32),
+        "u64" => Some(64),
+        _ => None,
+    }
+}
+
+fn get_range_policy(attrs: &[Attribute]) -> syn::Result<Option<RangePolicy>> {
+    let Some(lit) = get_string_value(attrs, "out_of_range")? else {
+        return Ok(None);
+    };
+    let policy = match lit.value().as_str() {
+        "truncate" => Some(RangePolicy::Truncate),
+        "saturate" => Some(RangePolicy::Saturate),
+        "error" => Some(RangePolicy::Error),
+        _ => None,
+    };
+    policy.map(Some).ok_or_else(|| {
+        syn::Error::new_spanned(
+            lit,
+            "`out_of_range` must be one of \"truncate\", \"saturate\" or \"error\"",
+        )
+    })
+}
+
+fn get_reserved_policy(attrs: &[Attribute]) -> syn::Result<ReservedPolicy> {
+    let Some(lit) = get_string_value(attrs, "reserved")? else {
+        return Ok(ReservedPolicy::Ignore);
+    };
+    let policy = match lit.value().as_str() {
+        "ignore" => Some(ReservedPolicy::Ignore),
+        "zero" => Some(ReservedPolicy::Zero),
+        "preserve" => Some(ReservedPolicy::Preserve),
+        _ => None,
+    };
+    policy.ok_or_else(|| {
+        syn::Error::new_spanned(
+            lit,
+            "`reserved` must be one of \"ignore\", \"zero\" or \"preserve\"",
+        )
+    })
+}
+
+/// Returns the kind and width of a field marked with `#[rbitpack(reserved = N)]`. The reserved
+/// bits are held by a `()` field, or by an unsigned integer field, which `reserved = "preserve"`
+/// needs to keep them.
+fn reserved_field<'a>(
+    field: &'a Field,
+    expr: &Expr,
+    policy: ReservedPolicy,
+) -> syn::Result<(FieldKind<'a>, usize)> {
+    let width = match usize_value(expr) {
+        Some(width) if (1..=64).contains(&width) => width,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                expr,
+                "`reserved` must be a number of bits between 1 and 64",
+            ))
+        }
+    };
+    let ty = match int_width(&field.ty) {
+        Some(type_width) if width > type_width => {
+            return Err(syn::Error::new_spanned(
+                expr,
+                format!(
+                    "`reserved` must be at most {}, the width of the field type",
+                    type_width
+                ),
+            ));
+        }
+        Some(_) => Some(&field.ty),
+        None if matches!(&field.ty, Type::Tuple(tuple) if tuple.elems.is_empty()) => None,
+        None => {
+            return Err(syn::Error::new_spanned(
+                &field.ty,
+                "reserved bits can only be held by a `()` field, or by a `u8`, `u16`, `u32` or `u64` field",
+            ));
+        }
+    };
+    if ty.is_none() && matches!(policy, ReservedPolicy::Preserve) {
+        return Err(syn::Error::new_spanned(
+            &field.ty,
+            "`reserved = \"preserve\"` needs an unsigned integer field to keep the reserved bits in",
+        ));
+    }
+    Ok((FieldKind::Reserved { ty, policy }, width))
+}
+
+fn get_bit_order(attrs: &[Attribute]) -> syn::Result<BitOrder> {
+    match get_string_value(attrs, "order")? {
+        None => Ok(BitOrder::Lsb0),
+        Some(lit_str) if lit_str.value() == "lsb0" => Ok(BitOrder::Lsb0),
+        Some(lit_str) if lit_str.value() == "msb0" => Ok(BitOrder::Msb0),
+        Some(lit) => Err(syn::Error::new_spanned(
+            lit,
+            "`order` must be one of \"lsb0\" or \"msb0\"",
+        )),
+    }
+}
+
+fn usize_value(expr: &Expr) -> Option<usize> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(lit_int),
+            ..
+        }) => lit_int.base10_parse().ok(),
+        _ => None,
+    }
+}
+
+/// Parses the value of a `bits` attribute: either a number of bits (`bits = 3`), or the range of
+/// bits used by the field (`bits = 4..8` or `bits = 4..=7`). Returns the first bit of the range,
+/// if one is given, and the number of bits.
+fn parse_bits(expr: &Expr) -> syn::Result<(Option<usize>, usize)> {
+    let bits = match expr {
+        Expr::Range(range) => {
+            let start = range.from.as_deref().and_then(usize_value);
+            let end = range.to.as_deref().and_then(usize_value);
+            match (start, end, &range.limits) {
+                (Some(start), Some(end), RangeLimits::HalfOpen(_)) if end > start => {
+                    Some((Some(start), end - start))
+                }
+                (Some(start), Some(end), RangeLimits::Closed(_)) if end >= start => {
+                    Some((Some(start), end - start + 1))
+                }
+                _ => None,
+            }
+        }
+        _ => usize_value(expr)
+            .filter(|width| *width > 0)
+            .map(|width| (None, width)),
+    };
+    bits.ok_or_else(|| {
+        syn::Error::new_spanned(
+            expr,
+            "`bits` must be a number of bits, such as `bits = 3`, or a non-empty range of bits, such as `bits = 4..8`",
+        )
+    })
+}
+
+/// Returns the kind of a field marked with `#[rbitpack(enum)]` or `#[rbitpack(nested)]`, together
+/// with the `BITS` constant giving its width and a description of its type for error messages.
+fn typed_kind<'a>(
+    field: &'a Field,
+    krate: &Path,
+) -> syn::Result<Option<(FieldKind<'a>, TokenStream, &'static str)>> {
+    let ty = &field.ty;
+    Ok(if has_attribute_flag(&field.attrs, "enum")? {
+        Some((
+            FieldKind::Enum { ty },
+            quote!(<#ty as #krate::BitEnum>::BITS),
+            "enum",
+        ))
+    } else if has_attribute_flag(&field.attrs, "nested")? {
+        Some((
+            FieldKind::Nested { ty },
+            quote!(<#ty as #krate::BitPack>::BITS),
+            "struct",
+        ))
+    } else {
+        None
+    })
+}
+
+/// Collects the packed fields of a struct and assigns them their bits.
+///
+/// `bool` fields take one bit each, unsigned integer fields annotated with
+/// `#[rbitpack(bits = N)]` take `N` bits, and fields annotated with `#[rbitpack(enum)]` or
+/// `#[rbitpack(nested)]` take the `BitEnum::BITS` or `BitPack::BITS` of their type, and fields
+/// annotated with `#[rbitpack(reserved = N)]` reserve `N` bits. Other fields, and fields marked with `#[rbitpack(skip)]`, are
+/// not packed.
+///
+/// Fields are placed right after the previous packed field, starting from bit 0, unless their
+/// position is pinned with `#[rbitpack(bit = N)]` or a range such as `#[rbitpack(bits = 4..8)]`.
+/// Pinned fields may not overlap any other field.
+pub(crate) fn collect_fields<'a>(
+    data: &'a DataStruct,
+    struct_attrs: &[Attribute],
+    krate: &Path,
+) -> syn::Result<Layout<'a>> {
+    let default_policy = get_range_policy(struct_attrs)?.unwrap_or(RangePolicy::Truncate);
+    let reserved_policy = get_reserved_policy(struct_attrs)?;
+
+    let mut fields = Vec::new();
+    let mut skipped = Vec::new();
+    let mut checks = Vec::new();
+    let mut offset = BitCount::default();
+    for (index, field) in data.fields.iter().enumerate() {
+        check_attributes(&field.attrs, FIELD_FLAGS, FIELD_KEYS)?;
+        let (member, ident) = match &field.ident {
+            Some(ident) => (Member::Named(ident.clone()), ident.clone()),
+            None => (
+                Member::Unnamed(Index {
+                    index: index as u32,
+                    span: field.ty.span(),
+                }),
+                format_ident!("field_{}", index),
+            ),
+        };
+        let bits = get_attribute_value::<Expr>(&field.attrs, "bits")?
+            .map(|expr| parse_bits(&expr).map(|bits| (expr, bits)))
+            .transpose()?;
+        let bit = get_attribute_value::<Expr>(&field.attrs, "bit")?;
+        let ty = &field.ty;
+
+        let (kind, width) = if has_attribute_flag(&field.attrs, "skip")? {
+            reject_layout_attributes(
+                field,
+                bits.as_ref().map(|(expr, _)| expr),
+                bit.as_ref(),
+                "cannot be combined with `skip`",
+            )?;
+            skipped.push(SkippedField::new(member, field)?);
+            continue;
+        } else if let Some(expr) = get_attribute_value::<Expr>(&field.attrs, "reserved")? {
+            if bits.is_some() || bit.is_some() || typed_kind(field, krate)?.is_some() {
+                return Err(syn::Error::new_spanned(
+                    expr,
+                    "`reserved` cannot be combined with `bits`, `bit`, `enum` or `nested`",
+                ));
+            }
+            let (kind, width) = reserved_field(field, &expr, reserved_policy)?;
+            (kind, BitCount::from_known(width))
+        } else if let Some((kind, type_bits, description)) = typed_kind(field, krate)? {
+            if matches!(kind, FieldKind::Nested { .. }) {
+                let message = format!(
+                    "struct '{}' of field '{}' must fit in 64 bits to be nested",
+                    quote!(#ty),
+                    member_name(&member),
+                );
+                checks.push(quote! {
+                    ::core::assert!(#type_bits <= 64, #message)
+                });
+                let message = format!(
+                    "struct '{}' of field '{}' needs a fixed `size` to be nested, not `size = \"auto\"`",
+                    quote!(#ty),
+                    member_name(&member),
+                );
+                checks.push(quote! {
+                    ::core::assert!(
+                        <<#ty as #krate::BitPack>::Packed as #krate::__private::NestedPacked>::FIXED,
+                        #message
+                    )
+                });
+            }
+            let width = match &bits {
+                // `bitval` moves the bits of enum and nested fields as `u64`.
+                Some((expr, (_, width))) if *width > 64 => {
+                    return Err(syn::Error::new_spanned(
+                        expr,
+                        "`bits` must be at most 64 for `enum` and `nested` fields",
+                    ));
+                }
+                // The field reserves a fixed number of bits, which must be enough for its type.
+                Some((_, (_, width))) => {
+                    let message = format!(
+                        "{} '{}' of field '{}' needs more bits than the {} given by `bits`",
+                        description,
+                        quote!(#ty),
+                        member_name(&member),
+                        width
+                    );
+                    checks.push(quote! {
+                        ::core::assert!(#type_bits <= #width, #message)
+                    });
+                    BitCount::from_known(*width)
+                }
+                None => BitCount {
+                    known: 0,
+                    terms: vec![type_bits],
+                },
+            };
+            (kind, width)
+        } else if let Some((expr, (_, width))) = &bits {
+            let Some(type_width) = int_width(&field.ty) else {
+                return Err(syn::Error::new_spanned(
+                    &field.ty,
+                    "`bits` can only be used on `u8`, `u16`, `u32` or `u64` fields, or on `enum` fields",
+                ));
+            };
+            if *width > type_width {
+                return Err(syn::Error::new_spanned(
+                    expr,
+                    format!(
+                        "`bits` must be at most {}, the width of the field type",
+                        type_width
+                    ),
+                ));
+            }
+            let policy = get_range_policy(&field.attrs)?.unwrap_or(default_policy);
+            (
+                FieldKind::Int {
+                    ty: &field.ty,
+                    policy,
+                },
+                BitCount::from_known(*width),
+            )
+        } else if matches!(&field.ty, Type::Path(syn::TypePath { path, .. }) if path.is_ident("bool"))
+        {
+            (FieldKind::Bool, BitCount::from_known(1))
+        } else {
+            reject_layout_attributes(
+                field,
+                None,
+                bit.as_ref(),
+                "only applies to packed fields; integer fields need `bits` to be packed",
+            )?;
+            skipped.push(SkippedField::new(member, field)?);
+            continue;
+        };
+
+        if let Some(lit) = get_string_value(&field.attrs, "default")? {
+            return Err(syn::Error::new_spanned(
+                lit,
+                "`default` only applies to fields that are not packed; add `skip` to leave this field out",
+            ));
+        }
+
+        let pinned = match (bits, bit) {
+            (Some((expr, (Some(_), _))), Some(bit)) => {
+                let mut error =
+                    syn::Error::new_spanned(bit, "`bit` cannot be combined with a range of `bits`");
+                error.combine(syn::Error::new_spanned(
+                    expr,
+                    "the range of `bits` is given here",
+                ));
+                return Err(error);
+            }
+            (Some((expr, (Some(start), _))), None) => Some((expr, start)),
+            (_, Some(bit)) => {
+                let Some(start) = usize_value(&bit) else {
+                    return Err(syn::Error::new_spanned(
+                        bit,
+                        "`bit` must be the index of a bit, such as `bit = 5`",
+                    ));
+                };
+                Some((bit, start))
+            }
+            _ => None,
+        };
+        let (pinned, offset_here) = match pinned {
+            Some((expr, start)) => (Some(expr), BitCount::from_known(start)),
+            None => (None, offset),
+        };
+
+        let next = {
+            let mut next = offset_here.clone();
+            next.add(&width);
+            next
+        };
+        fields.push(PackedField {
+            field,
+            member,
+            local: format_ident!("__rbitpack_field_{}", index),
+            ident,
+            kind,
+            offset: offset_here,
+            width,
+            pinned,
+        });
+        offset = next;
+    }
+
+    if fields.iter().any(|f| f.pinned.is_some()) {
+        check_overlaps(&fields, &mut checks)?;
+    }
+
+    Ok(Layout {
+        style: &data.fields,
+        packed: fields,
+        skipped,
+        checks,
+        order: get_bit_order(struct_attrs)?,
+    })
+}
+
+/// Rejects `bits`, `bit` and `out_of_range` on a field that is left out of the packed value,
+/// where they would be silently ignored. `reason` completes the error message.
+fn reject_layout_attributes(
+    field: &Field,
+    bits: Option<&Expr>,
+    bit: Option<&Expr>,
+    reason: &str,
+) -> syn::Result<()> {
+    let out_of_range = get_attribute_value::<Expr>(&field.attrs, "out_of_range")?;
+    let found = [
+        ("bits", bits),
+        ("bit", bit),
+        ("out_of_range", out_of_range.as_ref()),
+    ]
+    .into_iter()
+    .find_map(|(key, expr)| expr.map(|expr| (key, expr)));
+    match found {
+        Some((key, expr)) => Err(syn::Error::new_spanned(
+            expr,
+            format!("`{}` {}", key, reason),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Rejects fields sharing any bit. Overlaps that depend on the width of enum fields are checked
+/// by the compiler instead, through constant assertions added to `checks`.
+fn check_overlaps(fields: &[PackedField], checks: &mut Vec<TokenStream>) -> syn::Result<()> {
+    let mut errors: Option<syn::Error> = None;
+    for (i, later) in fields.iter().enumerate() {
+        for earlier in &fields[..i] {
+            let (start_a, end_a) = (&earlier.offset, earlier.end());
+            let (start_b, end_b) = (&later.offset, later.end());
+            match (
+                start_a.value(),
+                end_a.value(),
+                start_b.value(),
+                end_b.value(),
+            ) {
+                (Some(start_a), Some(end_a), Some(start_b), Some(end_b)) => {
+                    if start_a < end_b && start_b < end_a {
+                        let error = syn::Error::new_spanned(
+                            later
+                                .pinned
+                                .as_ref()
+                                .map_or(later.field.to_token_stream(), |e| e.to_token_stream()),
+                            format!(
+                                "field '{}' (bits {}..{}) overlaps field '{}' (bits {}..{})",
+                                later.name(),
+                                start_b,
+                                end_b,
+                                earlier.name(),
+                                start_a,
+                                end_a
+                            ),
+                        );
+                        match &mut errors {
+                            Some(errors) => errors.combine(error),
+                            None => errors = Some(error),
+                        }
+                    }
+                }
+                _ => {
+                    let message = format!(
+                        "field '{}' overlaps field '{}'",
+                        later.name(),
+                        earlier.name()
+                    );
+                    checks.push(quote! {
+                        ::core::assert!(
+                            !(#start_a < #end_b && #start_b < #end_a),
+                            #message
+                        )
+                    });
+                }
+            }
+        }
+    }
+
+    match errors {
+        Some(errors) => Err(errors),
+        None => Ok(()),
+    }
+}
+
+/// Total number of bits needed to pack all the fields.
+pub(crate) fn total_bits(fields: &[PackedField]) -> BitCount {
+    let ends: Vec<BitCount> = fields.iter().map(PackedField::end).collect();
+    if let Some(ends) = ends.iter().map(BitCount::value).collect::<Option<Vec<_>>>() {
+        return BitCount::from_known(ends.into_iter().max().unwrap_or(0));
+    }
+
+    // Without pinned fields, the last field ends after every other one.
+    if fields.iter().all(|f| f.pinned.is_none()) {
+        return ends.last().cloned().unwrap_or_default();
+    }
+
+    BitCount {
+        known: 0,
+        terms: vec![quote! {{
+            let mut bits = 0;
+            #(
+                if #ends > bits {
+                    bits = #ends;
+                }
+            )*
+            bits
+        }}],
+    }
+}
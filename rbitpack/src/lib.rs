@@ -1,61 +1,1200 @@
 extern crate proc_macro;
+extern crate proc_macro2;
 extern crate quote;
 extern crate syn;
+
+mod attrs;
+mod bit_enum;
+mod bit_flags;
+mod convert;
+mod layout;
+mod packed_type;
+mod serde_impl;
+
+use attrs::{
+    check_attributes, get_crate_path, get_endian, get_overflow_type, get_packed_type_name,
+    get_packing_size, get_serde_mode, has_attribute_flag, Endian, STRUCT_KEYS,
+};
+use layout::{
+    collect_fields, low_mask, total_bits, BitOrder, FieldKind, Layout, PackedField, RangePolicy,
+    ReservedPolicy,
+};
 use proc_macro::TokenStream;
-use quote::{quote, ToTokens};
-use syn::{parse_macro_input, Attribute, Data, DeriveInput, Field, Lit, Meta, NestedMeta, Type};
-fn get_attribute_value<T>(attrs: &[Attribute], key: &str) -> Option<T>
-where
-    T: syn::parse::Parse,
-{
-    for attr in attrs {
-        // Parse the attribute to Meta
-        let meta = attr.parse_meta().ok()?;
-
-        // Check if the attribute is a list
-        let Meta::List(meta_list) = meta else {
+use proc_macro2::Literal;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{parse_macro_input, Data, DeriveInput, Ident, Path};
+
+/// The integer a struct is packed into in the fixed-width modes.
+struct FixedWidth {
+    /// The unsigned integer the bits are assembled in.
+    unsigned: proc_macro2::TokenStream,
+    /// The integer exposed by the generated API: the unsigned one, or its signed counterpart
+    /// holding the same bits.
+    output: proc_macro2::TokenStream,
+    signed: bool,
+    bits: usize,
+}
+
+impl FixedWidth {
+    fn new(bits: usize, signed: bool) -> Self {
+        let unsigned = format_ident!("u{}", bits);
+        let output = format_ident!("{}{}", if signed { "i" } else { "u" }, bits);
+        FixedWidth {
+            unsigned: quote!(#unsigned),
+            output: quote!(#output),
+            signed,
+            bits,
+        }
+    }
+
+    /// Names the output integer in error messages, such as "a u16 (16 bits)".
+    fn describe(&self) -> String {
+        let article = if self.signed { "an" } else { "a" };
+        format!("{} {} ({} bits)", article, self.output, self.bits)
+    }
+
+    /// Reinterprets `value`, of the unsigned integer, as the output integer.
+    fn to_output(&self, value: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let output = &self.output;
+        if self.signed {
+            quote!((#value) as #output)
+        } else {
+            value
+        }
+    }
+
+    /// Reinterprets `value`, of the output integer, as the unsigned integer.
+    fn to_unsigned(&self, value: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let unsigned = &self.unsigned;
+        if self.signed {
+            quote!((#value) as #unsigned)
+        } else {
+            value
+        }
+    }
+}
+
+/// Returns the header of an inherent impl block for the struct, carrying its generics.
+fn impl_header(input: &DeriveInput) -> proc_macro2::TokenStream {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    quote!(impl #impl_generics #name #ty_generics #where_clause)
+}
+
+/// Returns the statement that evaluates the layout checks of a generic struct. Its checks may
+/// depend on its parameters, so they live in the `LAYOUT_CHECKS` associated constant and only run
+/// once the methods using them are instantiated. Other structs check their layout in free
+/// constants instead.
+fn layout_check(input: &DeriveInput) -> proc_macro2::TokenStream {
+    if input.generics.params.is_empty() {
+        quote!()
+    } else {
+        quote!(let () = Self::LAYOUT_CHECKS;)
+    }
+}
+
+/// Builds the compile error reported when a struct has more fields than fit in the chosen
+/// fixed-width integer and `overflow` is not enabled. The error points at the struct and at the
+/// first field that does not fit.
+fn overflow_error(name: &Ident, fields: &[PackedField], fixed: &FixedWidth) -> syn::Error {
+    let mut error = syn::Error::new_spanned(
+        name,
+        format!(
+            "struct '{}' needs {} bits, which do not fit in {}; \
+             use a larger `size` or set `overflow = true`",
+            name,
+            total_bits(fields).known,
+            fixed.describe()
+        ),
+    );
+    if let Some(field) = fields
+        .iter()
+        .find(|f| matches!(f.end().value(), Some(end) if end > fixed.bits))
+    {
+        error.combine(syn::Error::new_spanned(
+            field.field,
+            format!(
+                "field does not fit in {} packed from struct '{}'",
+                fixed.describe(),
+                name
+            ),
+        ));
+    }
+    error
+}
+
+/// Rejects fields pinned with `bit` or `bits` beyond the bits of the chosen fixed-width integer,
+/// whatever the `overflow` setting. Returns constant assertions for the pinned fields placed
+/// after an enum, whose position is only known to the compiler.
+fn pinned_range_checks(
+    name: &Ident,
+    fields: &[PackedField],
+    fixed: &FixedWidth,
+) -> syn::Result<Vec<proc_macro2::TokenStream>> {
+    let max_bits = fixed.bits;
+    let mut checks = Vec::new();
+    for field in fields {
+        let Some(pinned) = &field.pinned else {
             continue;
         };
+        let end = field.end();
+        match end.value() {
+            Some(end) if end > max_bits => {
+                return Err(syn::Error::new_spanned(
+                    pinned,
+                    format!(
+                        "bits {}..{} of field '{}' are out of range for {}",
+                        field.offset.known,
+                        end,
+                        field.name(),
+                        fixed.describe()
+                    ),
+                ));
+            }
+            Some(_) => {}
+            None => {
+                let message = format!(
+                    "field '{}' of struct '{}' is out of range for {}",
+                    field.name(),
+                    name,
+                    fixed.describe()
+                );
+                checks.push(quote! {
+                    ::core::assert!(#end <= #max_bits, #message)
+                });
+            }
+        }
+    }
+    Ok(checks)
+}
 
-        // Check if the attribute is `rbitpack`
-        if !meta_list.path.is_ident("rbitpack") {
-            continue;
+/// Returns the expression `pack` stores for `value`, the value of an integer field, after
+/// applying the field's out-of-range policy.
+fn int_value(
+    name: &Ident,
+    field: &PackedField,
+    ty: &syn::Type,
+    policy: RangePolicy,
+    value: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let max = Literal::u128_unsuffixed(field.max_value());
+    match policy {
+        RangePolicy::Truncate => value,
+        RangePolicy::Saturate => quote! {
+            if #value > #max { #max } else { #value }
+        },
+        RangePolicy::Error => {
+            let message = format!(
+                "Value {{}} of field '{}' in struct '{}' does not fit in {} bits.",
+                field.name(),
+                name,
+                field.width.known
+            );
+            quote! {
+                {
+                    let value: #ty = #value;
+                    if value > #max {
+                        ::core::panic!(#message, value);
+                    }
+                    value
+                }
+            }
         }
+    }
+}
+
+/// Returns the expression `pack` stores for `value`, the value of a field: a `bool`, an
+/// unsigned integer, an enum, a nested struct, or reserved bits, which are only kept with
+/// `reserved = "preserve"`.
+fn pack_value(
+    name: &Ident,
+    field: &PackedField,
+    krate: &Path,
+    value: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    match &field.kind {
+        FieldKind::Bool => value,
+        FieldKind::Int { ty, policy } => int_value(name, field, ty, *policy, value),
+        FieldKind::Enum { .. } => quote!(#krate::BitEnum::to_bits(&#value)),
+        FieldKind::Nested { .. } => quote! {
+            #krate::__private::NestedPacked::to_bits(#krate::BitPack::pack(&#value))
+        },
+        FieldKind::Reserved {
+            policy: ReservedPolicy::Preserve,
+            ..
+        } => value,
+        FieldKind::Reserved { .. } => quote!(0),
+    }
+}
 
-        // Find the specified key-value pair
-        for nested_meta in meta_list.nested.iter() {
-            if let NestedMeta::Meta(Meta::NameValue(name_value)) = nested_meta {
-                if name_value.path.is_ident(key) {
-                    return syn::parse2(name_value.lit.to_token_stream()).ok();
+/// Where the bits of a field sit in a fixed-width integer.
+struct FieldBits {
+    /// The mask selecting the bits of the field once shifted down to bit 0.
+    mask: proc_macro2::TokenStream,
+    /// How far the bits of the field are shifted up from bit 0 of the integer.
+    shift: proc_macro2::TokenStream,
+    /// A condition, only known to the compiler, that holds when the field fits in the integer.
+    /// Only set with `overflow = true`; otherwise a constant assertion rules out fields that do
+    /// not fit.
+    guard: Option<proc_macro2::TokenStream>,
+}
+
+/// Returns where the bits of a field sit in a fixed-width integer of `max_bits` bits, numbered
+/// in the given order, or `None` when the field is known not to fit.
+///
+/// In `lsb0` order, a field that only partly fits keeps its low bits. In `msb0` order, the field
+/// is counted from the most significant bit, and is dropped as a whole when it does not fit.
+fn field_bits(
+    field: &PackedField,
+    order: BitOrder,
+    packed_ty: &proc_macro2::TokenStream,
+    max_bits: usize,
+    overflow: bool,
+) -> Option<FieldBits> {
+    let offset = &field.offset;
+    let end = field.end();
+    match order {
+        BitOrder::Lsb0 => {
+            // Bits shifted past `max_bits` are dropped, so the mask only needs clipping when the
+            // literal would not fit in the packed type.
+            let mask = match (field.offset.value(), field.width.value()) {
+                (Some(offset), _) if offset >= max_bits => return None,
+                (Some(offset), Some(width)) => {
+                    let mask = Literal::u128_unsuffixed(low_mask(width.min(max_bits - offset)));
+                    quote!(#mask)
+                }
+                (None, Some(width)) => {
+                    let mask = Literal::u128_suffixed(low_mask(width));
+                    quote!((#mask as #packed_ty))
+                }
+                (_, None) => {
+                    let width = &field.width;
+                    quote!((((1u128 << #width) - 1) as #packed_ty))
+                }
+            };
+            Some(FieldBits {
+                mask,
+                shift: quote!(#offset),
+                guard: (field.offset.value().is_none() && overflow)
+                    .then(|| quote!(#offset < #max_bits)),
+            })
+        }
+        BitOrder::Msb0 => {
+            let mask = match field.width.value() {
+                Some(width) if end.value().is_some() => {
+                    let mask = Literal::u128_unsuffixed(low_mask(width));
+                    quote!(#mask)
+                }
+                Some(width) => {
+                    let mask = Literal::u128_suffixed(low_mask(width));
+                    quote!((#mask as #packed_ty))
+                }
+                None => {
+                    let width = &field.width;
+                    quote!((((1u128 << #width) - 1) as #packed_ty))
+                }
+            };
+            match end.value() {
+                Some(end) if end > max_bits => None,
+                Some(end) => {
+                    let shift = max_bits - end;
+                    Some(FieldBits {
+                        mask,
+                        shift: quote!(#shift),
+                        guard: None,
+                    })
                 }
+                None => Some(FieldBits {
+                    mask,
+                    shift: quote!((#max_bits - #end)),
+                    guard: overflow.then(|| quote!(#end <= #max_bits)),
+                }),
             }
         }
     }
+}
 
-    None
+/// Returns the expression `unpack` uses to rebuild a field from `raw`, an unsigned integer
+/// holding the bits of the field. Enum fields panic on discriminants their type does not map,
+/// or return an `UnpackError` from the enclosing function when `fallible` is set. Nested structs
+/// are rebuilt through their own `unpack` or `try_unpack`.
+fn unpack_value(
+    name: &Ident,
+    field: &PackedField,
+    krate: &Path,
+    raw: proc_macro2::TokenStream,
+    fallible: bool,
+) -> proc_macro2::TokenStream {
+    match &field.kind {
+        FieldKind::Bool => quote!((#raw) != 0),
+        FieldKind::Int { ty, .. } => quote!((#raw) as #ty),
+        FieldKind::Enum { ty } => {
+            let invalid = if fallible {
+                let field_name = field.name();
+                let offset = &field.offset;
+                quote! {
+                    return ::core::result::Result::Err(#krate::UnpackError::InvalidValue {
+                        struct_name: ::core::stringify!(#name),
+                        field: #field_name,
+                        bit: #offset,
+                        value: raw,
+                    })
+                }
+            } else {
+                let message = format!(
+                    "Invalid value {{}} for field '{}' in struct '{}'.",
+                    field.name(),
+                    name
+                );
+                quote!(::core::panic!(#message, raw))
+            };
+            quote! {
+                {
+                    let raw = (#raw) as u64;
+                    match <#ty as #krate::BitEnum>::from_bits(raw) {
+                        ::core::option::Option::Some(value) => value,
+                        ::core::option::Option::None => #invalid,
+                    }
+                }
+            }
+        }
+        FieldKind::Nested { ty } => {
+            let packed = quote! {
+                <<#ty as #krate::BitPack>::Packed as #krate::__private::NestedPacked>::from_bits(
+                    (#raw) as u64,
+                )
+            };
+            if fallible {
+                quote! {
+                    match <#ty as #krate::BitPack>::try_unpack(#packed) {
+                        ::core::result::Result::Ok(value) => value,
+                        ::core::result::Result::Err(error) => {
+                            return ::core::result::Result::Err(error)
+                        }
+                    }
+                }
+            } else {
+                quote!(<#ty as #krate::BitPack>::unpack(#packed))
+            }
+        }
+        FieldKind::Reserved { ty, policy } => {
+            let empty = match ty {
+                Some(_) => quote!(0),
+                None => quote!(()),
+            };
+            match (ty, policy) {
+                (Some(ty), ReservedPolicy::Preserve) => quote!((#raw) as #ty),
+                (_, ReservedPolicy::Zero) => {
+                    let set = if fallible {
+                        let field_name = field.name();
+                        let offset = &field.offset;
+                        quote! {
+                            return ::core::result::Result::Err(#krate::UnpackError::InvalidValue {
+                                struct_name: ::core::stringify!(#name),
+                                field: #field_name,
+                                bit: #offset,
+                                value: raw,
+                            })
+                        }
+                    } else {
+                        let message = format!(
+                            "Reserved bits of field '{}' in struct '{}' are set.",
+                            field.name(),
+                            name
+                        );
+                        quote!(::core::panic!(#message))
+                    };
+                    // The block of a `()` field evaluates to `()` on its own.
+                    let value = ty.map(|_| quote!(0));
+                    quote! {
+                        {
+                            let raw = (#raw) as u64;
+                            if raw != 0 {
+                                #set
+                            }
+                            #value
+                        }
+                    }
+                }
+                _ => empty,
+            }
+        }
+    }
 }
 
-fn get_packing_type(attrs: &[Attribute]) -> Option<String> {
-    get_attribute_value::<Lit>(attrs, "size").and_then(|lit| match lit {
-        Lit::Str(lit_str) => Some(lit_str.value()),
-        _ => None,
+/// Generates the associated constants describing the layout: `PACKED_BITS`, and `A_BIT` for each
+/// packed field `a`. In the fixed-width modes, `fixed_width` holds the packed integer and its
+/// bits, and the constants also include `A_MASK` for each field and `ALL_MASK`.
+fn layout_constants(
+    input: &DeriveInput,
+    layout: &Layout,
+    fixed_width: Option<&FixedWidth>,
+    overflow: bool,
+    packed_bits: &proc_macro2::TokenStream,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let mut constants = Vec::new();
+    let mut masks = Vec::new();
+    for field in &layout.packed {
+        let upper = field.ident.unraw().to_string().to_uppercase();
+        if upper == "ALL" && fixed_width.is_some() {
+            return Err(syn::Error::new_spanned(
+                &field.ident,
+                format!(
+                    "field '{}' would define a mask clashing with `{}::ALL_MASK`",
+                    field.name(),
+                    name
+                ),
+            ));
+        }
+
+        let offset = &field.offset;
+        let bit = format_ident!("{}_BIT", upper);
+        let bit_doc = format!(
+            " The index of the first bit of the `{}` field.",
+            field.name()
+        );
+        constants.push(quote! {
+            #[doc = #bit_doc]
+            pub const #bit: usize = #offset;
+        });
+
+        let Some(fixed) = fixed_width else {
+            continue;
+        };
+        let mask_name = format_ident!("{}_MASK", upper);
+        let mask_doc = format!(
+            " The bits of the `{}` field in the packed value.",
+            field.name()
+        );
+        let mask = match field_bits(field, layout.order, &fixed.unsigned, fixed.bits, overflow) {
+            None => quote!(0),
+            Some(FieldBits {
+                mask,
+                shift,
+                guard: None,
+            }) => quote!(#mask << #shift),
+            Some(FieldBits {
+                mask,
+                shift,
+                guard: Some(guard),
+            }) => quote!(if #guard { #mask << #shift } else { 0 }),
+        };
+        let (unsigned, output) = (&fixed.unsigned, &fixed.output);
+        // Build the mask as unsigned, so its literals never overflow the signed type
+        let mask = if fixed.signed {
+            fixed.to_output(quote!({
+                let mask: #unsigned = #mask;
+                mask
+            }))
+        } else {
+            mask
+        };
+        constants.push(quote! {
+            #[doc = #mask_doc]
+            pub const #mask_name: #output = #mask;
+        });
+        masks.push(mask_name);
+    }
+
+    if let Some(fixed) = fixed_width {
+        let output = &fixed.output;
+        constants.push(quote! {
+            /// The bits of every packed field in the packed value.
+            pub const ALL_MASK: #output = 0 #(| Self::#masks)*;
+        });
+    }
+
+    let header = impl_header(input);
+    Ok(quote! {
+        #header {
+            /// The number of bits occupied by the packed fields.
+            pub const PACKED_BITS: usize = #packed_bits;
+
+            /// The number of bytes needed to hold the packed fields.
+            pub const PACKED_BYTES: usize = Self::PACKED_BITS.div_ceil(8);
+
+            #(#constants)*
+        }
     })
 }
 
-fn get_overflow_type(attrs: &[Attribute]) -> Option<bool> {
-    get_attribute_value::<Lit>(attrs, "overflow").and_then(|lit| match lit {
-        Lit::Bool(lit_bool) => Some(lit_bool.value),
-        _ => None,
-    })
+/// Generates `pack_bytes`, `unpack_bytes` and `try_unpack_bytes`, converting the packed value to
+/// and from `PACKED_BYTES` bytes in the given byte order. `fixed_width` holds the packed integer
+/// in the fixed-width modes.
+///
+/// The bytes follow the bit order: in `lsb0` order, they are naturally little-endian, starting
+/// from the byte holding bit 0; in `msb0` order, they are naturally big-endian. The other byte
+/// order reverses them.
+fn byte_methods(
+    input: &DeriveInput,
+    layout: &Layout,
+    krate: &Path,
+    fixed_width: Option<&FixedWidth>,
+    endian: Endian,
+    packed_bits: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let name = &input.ident;
+    // Array lengths cannot refer to the constants of a generic struct, so spell out its length.
+    let packed_bytes = if input.generics.params.is_empty() {
+        quote!(#name::PACKED_BYTES)
+    } else {
+        quote!({
+            let bits: usize = #packed_bits;
+            bits.div_ceil(8)
+        })
+    };
+    let bytes_ty = quote!([u8; #packed_bytes]);
+    let natural = match layout.order {
+        BitOrder::Lsb0 => Endian::Little,
+        BitOrder::Msb0 => Endian::Big,
+    };
+    let reverse = (endian != natural).then(|| quote!(bytes.reverse();));
+    let reverse_input = (endian != natural).then(|| {
+        quote! {
+            let mut bytes = bytes;
+            bytes.reverse();
+        }
+    });
+    let (to_bytes, from_bytes) = match natural {
+        Endian::Little => (quote!(to_le_bytes), quote!(from_le_bytes)),
+        Endian::Big => (quote!(to_be_bytes), quote!(from_be_bytes)),
+    };
+
+    let (pack_bytes, packed_from_bytes) = match fixed_width {
+        Some(FixedWidth { output, .. }) => (
+            quote! {
+                let packed = self.pack().#to_bytes();
+                let mut bytes = [0u8; #packed_bytes];
+                bytes.copy_from_slice(&packed[..Self::PACKED_BYTES]);
+            },
+            quote! {
+                let mut packed = [0u8; ::core::mem::size_of::<#output>()];
+                packed[..Self::PACKED_BYTES].copy_from_slice(&bytes);
+                #output::#from_bytes(packed)
+            },
+        ),
+        None => {
+            let shift = match natural {
+                Endian::Little => quote!((index % 8) * 8),
+                Endian::Big => quote!(56 - (index % 8) * 8),
+            };
+            (
+                quote! {
+                    let mut bytes = [0u8; #packed_bytes];
+                    let packed = self.pack();
+                    for (byte, value) in bytes
+                        .iter_mut()
+                        .zip(packed.iter().flat_map(|part| part.#to_bytes()))
+                    {
+                        *byte = value;
+                    }
+                },
+                quote! {
+                    let mut packed = #krate::__private::vec![0u64; Self::PACKED_BYTES.div_ceil(8)];
+                    for (index, byte) in bytes.iter().enumerate() {
+                        packed[index / 8] |= (*byte as u64) << #shift;
+                    }
+                    packed
+                },
+            )
+        }
+    };
+
+    let endian_doc = match endian {
+        Endian::Little => " The bytes are in little-endian order.",
+        Endian::Big => " The bytes are in big-endian order.",
+    };
+
+    let header = impl_header(input);
+    quote! {
+        #header {
+            /// Packs the fields of the struct into `PACKED_BYTES` bytes, the fewest that hold every
+            /// packed bit.
+            #[doc = #endian_doc]
+            pub fn pack_bytes(&self) -> #bytes_ty {
+                #pack_bytes
+                #reverse
+                bytes
+            }
+
+            /// Unpacks the bytes produced by `pack_bytes` into the fields of the struct.
+            #[doc = #endian_doc]
+            pub fn unpack_bytes(bytes: #bytes_ty) -> Self {
+                #reverse_input
+                let packed = { #packed_from_bytes };
+                Self::unpack(packed)
+            }
+
+            /// Unpacks the bytes produced by `pack_bytes` like `unpack_bytes`, but returns an error
+            /// instead of panicking when they do not describe a valid value, as `try_unpack` does.
+            pub fn try_unpack_bytes(
+                bytes: #bytes_ty,
+            ) -> ::core::result::Result<Self, #krate::UnpackError> {
+                #reverse_input
+                let packed = { #packed_from_bytes };
+                Self::try_unpack(packed)
+            }
+        }
+    }
+}
+
+/// Generates the inherent `pack`/`unpack` methods for the fixed-width modes (`i8` to `u128`),
+/// packing the fields into a single unsigned integer of `max_bits` bits.
+fn fixed_width_methods(
+    input: &DeriveInput,
+    layout: &Layout,
+    krate: &Path,
+    overflow: bool,
+    fixed: &FixedWidth,
+    packed_bits: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let name = &input.ident;
+    let packed_ty = &fixed.unsigned;
+    let output_ty = &fixed.output;
+    let max_bits = fixed.bits;
+    let article = if max_bits == 8 { "an" } else { "a" };
+    let ty_name = output_ty.to_string();
+    let signedness = if fixed.signed { "signed" } else { "unsigned" };
+
+    let pack_doc = format!(
+        " Packs the fields of the struct into {} {}-bit {} integer ({}).",
+        article, max_bits, signedness, ty_name
+    );
+    let pack_doc_bits = format!(
+        " This method sets the bits of each field at its position in the resulting {}.",
+        ty_name
+    );
+    let pack_doc_overflow = format!(
+        " Fields beyond the first {} bits are only accepted with `overflow = true` and are not packed.",
+        max_bits
+    );
+    let pack_doc_returns = format!(
+        " - A `{}` holding the bits of every field in the struct.",
+        ty_name
+    );
+
+    let unpack_doc = format!(
+        " Unpacks {} {}-bit {} integer ({}) into the fields of the struct.",
+        article, max_bits, signedness, ty_name
+    );
+    let unpack_doc_bits = format!(
+        " This method reads the bits of each field from the given {} and assigns them to the corresponding field.",
+        ty_name
+    );
+    let unpack_doc_overflow = format!(
+        " Fields beyond the first {} bits are only accepted with `overflow = true` and are unpacked from zero bits.",
+        max_bits
+    );
+    let unpack_doc_param = format!(
+        " - `packed`: A `{}` holding the bits of the fields to be unpacked.",
+        ty_name
+    );
+
+    // The bits past the last field, and the index of the first one set.
+    let (stray, first_stray) = match layout.order {
+        BitOrder::Lsb0 => (
+            quote!(packed >> bits),
+            quote!(bits + (packed >> bits).trailing_zeros() as usize),
+        ),
+        BitOrder::Msb0 => (
+            quote!(packed & (#packed_ty::MAX >> bits)),
+            quote!((packed & (#packed_ty::MAX >> bits)).leading_zeros() as usize),
+        ),
+    };
+
+    // The index of the first bit set in `stray`, the bits outside every field.
+    let first_unmapped = match layout.order {
+        BitOrder::Lsb0 => quote!(stray.trailing_zeros() as usize),
+        BitOrder::Msb0 => quote!(stray.leading_zeros() as usize),
+    };
+
+    // Whether the bit at `bit` of `stray`, numbered in the layout's order, is set.
+    let stray_bit = match layout.order {
+        BitOrder::Lsb0 => quote!((stray >> bit) & 1 != 0),
+        BitOrder::Msb0 => quote!((stray >> (#max_bits - 1 - bit)) & 1 != 0),
+    };
+
+    let mut pack_fields = Vec::new();
+    let mut unpack_fields = Vec::new();
+    let mut try_unpack_fields = Vec::new();
+    let mut debug_fields = Vec::new();
+    for field in &layout.packed {
+        let local = &field.local;
+        let member = &field.member;
+        let field_name = field.name();
+        let is_bool = matches!(field.kind, FieldKind::Bool);
+        let empty = |fallible| unpack_value(name, field, krate, quote!(0), fallible);
+        let Some(FieldBits { mask, shift, guard }) =
+            field_bits(field, layout.order, packed_ty, max_bits, overflow)
+        else {
+            let (empty, try_empty) = (empty(false), empty(true));
+            unpack_fields.push(quote!(let #local = #empty;));
+            try_unpack_fields.push(quote!(let #local = #try_empty;));
+            debug_fields.push(quote!((#field_name, #is_bool, 0)));
+            continue;
+        };
+
+        let value = pack_value(name, field, krate, quote!(self.#member));
+        let pack_field = quote! {
+            result |= (((#value) as #packed_ty) & #mask) << #shift;
+        };
+        let unpack_field = |fallible| {
+            let value = unpack_value(
+                name,
+                field,
+                krate,
+                quote!((packed >> #shift) & #mask),
+                fallible,
+            );
+            // A field placed after an enum may only be known not to fit once the enum widths
+            // are; without `overflow`, a compile-time assertion rules that out.
+            match &guard {
+                Some(guard) => {
+                    let empty = empty(fallible);
+                    quote!(let #local = if #guard { #value } else { #empty };)
+                }
+                None => quote!(let #local = #value;),
+            }
+        };
+        match &guard {
+            Some(guard) => pack_fields.push(quote! {
+                if #guard {
+                    #pack_field
+                }
+            }),
+            None => pack_fields.push(pack_field),
+        }
+        unpack_fields.push(unpack_field(false));
+        try_unpack_fields.push(unpack_field(true));
+
+        let raw = quote!(((packed >> #shift) & #mask) as u128);
+        debug_fields.push(match &guard {
+            Some(guard) => quote!((#field_name, #is_bool, if #guard { #raw } else { 0 })),
+            None => quote!((#field_name, #is_bool, #raw)),
+        });
+    }
+    let constructor = layout.constructor();
+    let result = fixed.to_output(quote!(result));
+    let packed = fixed.to_unsigned(quote!(packed));
+    let all_mask = fixed.to_unsigned(quote!(Self::ALL_MASK));
+    let header = impl_header(input);
+    let check = layout_check(input);
+    let pack_const = layout.const_pack().then(|| quote!(const));
+    let unpack_const = layout.const_unpack().then(|| quote!(const));
+
+    quote! {
+        #header {
+            #[doc = #pack_doc]
+            #[doc = #pack_doc_bits]
+            #[doc = #pack_doc_overflow]
+            ///
+            /// # Returns
+            #[doc = #pack_doc_returns]
+            pub #pack_const fn pack(&self) -> #output_ty {
+                #check
+                let mut result: #packed_ty = 0;
+                #(#pack_fields)*
+                #result
+            }
+
+            #[doc = #unpack_doc]
+            #[doc = #unpack_doc_bits]
+            #[doc = #unpack_doc_overflow]
+            ///
+            /// # Parameters
+            #[doc = #unpack_doc_param]
+            ///
+            /// # Returns
+            /// - A new instance of the struct with its fields set according to the bits in `packed`.
+            pub #unpack_const fn unpack(packed: #output_ty) -> Self {
+                #check
+                let packed = #packed;
+                #(#unpack_fields)*
+                #constructor
+            }
+
+            /// Unpacks the fields of the struct like `unpack`, but returns an error instead of
+            /// panicking when `packed` does not describe a valid value.
+            ///
+            /// # Errors
+            /// - `UnpackError::UnexpectedBit` if a bit past the last field is set.
+            /// - `UnpackError::InvalidValue` if the bits of an enum field do not map to a variant,
+            ///   or if reserved bits that must be clear are set.
+            pub #unpack_const fn try_unpack(
+                packed: #output_ty,
+            ) -> ::core::result::Result<Self, #krate::UnpackError> {
+                #check
+                let packed = #packed;
+                let bits: usize = #packed_bits;
+                if bits < #max_bits && #stray != 0 {
+                    return ::core::result::Result::Err(#krate::UnpackError::UnexpectedBit {
+                        struct_name: ::core::stringify!(#name),
+                        bit: #first_stray,
+                    });
+                }
+
+                #(#try_unpack_fields)*
+                ::core::result::Result::Ok(#constructor)
+            }
+
+            /// Unpacks the fields of the struct like `try_unpack`, but also rejects the bits that
+            /// belong to no field, such as the bits between two pinned fields.
+            ///
+            /// # Errors
+            /// - `UnpackError::UnexpectedBit` if a bit outside `ALL_MASK` is set.
+            /// - The errors of `try_unpack` otherwise.
+            pub #unpack_const fn unpack_strict(
+                packed: #output_ty,
+            ) -> ::core::result::Result<Self, #krate::UnpackError> {
+                let stray = #packed & !#all_mask;
+                if stray != 0 {
+                    return ::core::result::Result::Err(#krate::UnpackError::UnexpectedBit {
+                        struct_name: ::core::stringify!(#name),
+                        bit: #first_unmapped,
+                    });
+                }
+                Self::try_unpack(packed)
+            }
+
+            /// Returns a value whose `Debug` output lists the fields set in `packed`, such as
+            /// `Example { a: true, c: true }`, followed by the indices of the bits set outside
+            /// every field, if any. Integer, enum and nested fields show their raw bits.
+            pub fn debug_packed(packed: #output_ty) -> impl ::core::fmt::Debug {
+                let packed = #packed;
+                let stray = packed & !#all_mask;
+                #krate::__private::DebugPacked {
+                    struct_name: ::core::stringify!(#name),
+                    fields: [#(#debug_fields),*],
+                    stray_bits: (0..#max_bits).filter(move |&bit| #stray_bit),
+                }
+            }
+        }
+    }
+}
+
+/// Generates the inherent `pack`/`unpack` methods for the `auto` mode, packing the fields into
+/// a `Vec<u64>` backed by `bitval::Bitfield`.
+fn auto_methods(
+    input: &DeriveInput,
+    layout: &Layout,
+    krate: &Path,
+    overflow: bool,
+) -> proc_macro2::TokenStream {
+    let name = &input.ident;
+    let num_bits = total_bits(&layout.packed);
+    let order = match layout.order {
+        BitOrder::Lsb0 => quote!(#krate::BitOrder::Lsb0),
+        BitOrder::Msb0 => quote!(#krate::BitOrder::Msb0),
+    };
+
+    let mut pack_fields = Vec::new();
+    let mut unpack_fields = Vec::new();
+    let mut try_unpack_fields = Vec::new();
+    let mut mask_fields = Vec::new();
+    let mut debug_fields = Vec::new();
+    let mut clear_fields = Vec::new();
+    for field in &layout.packed {
+        let local = &field.local;
+        let member = &field.member;
+        let offset = &field.offset;
+        let width = &field.width;
+        let end = field.end();
+        mask_fields.push(quote! {
+            for bit in #offset..#end {
+                mask.set(bit, true);
+            }
+        });
+        pack_fields.push(match &field.kind {
+            FieldKind::Bool => quote!(bitfield.set(#offset, self.#member);),
+            _ => {
+                let value = pack_value(name, field, krate, quote!(self.#member));
+                quote!(bitfield.set_bits(#offset, #width, (#value) as u64);)
+            }
+        });
+
+        let unpack_field = |fallible| {
+            let value = match &field.kind {
+                FieldKind::Bool => quote!(bitfield.get(#offset)),
+                _ => unpack_value(
+                    name,
+                    field,
+                    krate,
+                    quote!(bitfield.get_bits(#offset, #width)),
+                    fallible,
+                ),
+            };
+            // With overflow allowed, fields that are missing from a short vector are unpacked from zero bits.
+            if overflow {
+                let empty = unpack_value(name, field, krate, quote!(0), fallible);
+                quote! {
+                    let #local = if #end <= available_bits {
+                        #value
+                    } else {
+                        #empty
+                    };
+                }
+            } else {
+                quote!(let #local = #value;)
+            }
+        };
+        unpack_fields.push(unpack_field(false));
+        try_unpack_fields.push(unpack_field(true));
+
+        let field_name = field.name();
+        let (is_bool, raw) = match &field.kind {
+            FieldKind::Bool => (true, quote!(bitfield.get(#offset))),
+            _ => (false, quote!(bitfield.get_bits(#offset, #width))),
+        };
+        debug_fields.push(quote! {
+            (#field_name, #is_bool, if #end <= available_bits { #raw as u128 } else { 0 })
+        });
+        clear_fields.push(quote! {
+            for bit in #offset..::core::cmp::min(#end, available_bits) {
+                stray.set(bit, false);
+            }
+        });
+    }
+    let constructor = layout.constructor();
+    let header = impl_header(input);
+    let check = layout_check(input);
+
+    quote! {
+        #header {
+            /// Packs the fields of the struct into a vector of 64-bit unsigned integers (Vec<u64>).
+            /// This method sets the bits of each field at its position in the resulting vector.
+            /// The size of the vector is determined by the number of packed bits divided by 64, rounded up.
+            ///
+            /// # Returns
+            /// - A `Vec<u64>` holding the bits of every field in the struct.
+            pub fn pack(&self) -> #krate::__private::Vec<u64> {
+                #check
+                let mut bitfield = #krate::Bitfield::with_order(#num_bits, #order);
+                #(#pack_fields)*
+                bitfield.parts
+            }
+
+            /// Unpacks a vector of 64-bit unsigned integers (Vec<u64>) into the fields of the struct.
+            /// This method reads the bits of each field from the given vector and assigns them to the corresponding field.
+            /// If overflow is not allowed, it will panic if the struct has more bits than can be unpacked from the vector.
+            ///
+            /// # Parameters
+            /// - `packed`: A `Vec<u64>` holding the bits of the fields to be unpacked.
+            ///
+            /// # Returns
+            /// - A new instance of the struct with its fields set according to the bits in `packed`.
+            pub fn unpack(packed: #krate::__private::Vec<u64>) -> Self {
+                #check
+                let bitfield = #krate::Bitfield::from_parts(packed, #order);
+                let available_bits = bitfield.parts.len() * 64;
+
+                // Overflow check
+                if #num_bits > available_bits && !#overflow {
+                    ::core::panic!(
+                        "Overflow occurred during unpacking: struct '{}' has more bits than can be unpacked from the provided Bitfield size.",
+                        ::core::stringify!(#name)
+                    );
+                }
+
+                #(#unpack_fields)*
+                #constructor
+            }
+
+            /// Unpacks the fields of the struct like `unpack`, but returns an error instead of
+            /// panicking when `packed` does not describe a valid value.
+            ///
+            /// # Errors
+            /// - `UnpackError::TooShort` if overflow is not allowed and the vector holds fewer bits
+            ///   than the struct.
+            /// - `UnpackError::UnexpectedBit` if a bit past the last field is set.
+            /// - `UnpackError::InvalidValue` if the bits of an enum field do not map to a variant,
+            ///   or if reserved bits that must be clear are set.
+            pub fn try_unpack(
+                packed: #krate::__private::Vec<u64>,
+            ) -> ::core::result::Result<Self, #krate::UnpackError> {
+                #check
+                let bitfield = #krate::Bitfield::from_parts(packed, #order);
+                let available_bits = bitfield.parts.len() * 64;
+
+                if #num_bits > available_bits && !#overflow {
+                    return ::core::result::Result::Err(#krate::UnpackError::TooShort {
+                        struct_name: ::core::stringify!(#name),
+                        bits: #num_bits,
+                        available: available_bits,
+                    });
+                }
+                if let ::core::option::Option::Some(bit) = bitfield.first_set_from(#num_bits) {
+                    return ::core::result::Result::Err(#krate::UnpackError::UnexpectedBit {
+                        struct_name: ::core::stringify!(#name),
+                        bit,
+                    });
+                }
+
+                #(#try_unpack_fields)*
+                ::core::result::Result::Ok(#constructor)
+            }
+
+            /// Unpacks the fields of the struct like `try_unpack`, but also requires the vector
+            /// to hold exactly the parts produced by `pack`, and rejects the bits that belong to
+            /// no field, such as the bits between two pinned fields.
+            ///
+            /// # Errors
+            /// - `UnpackError::LengthMismatch` if the vector is shorter or longer than the packed
+            ///   one.
+            /// - `UnpackError::UnexpectedBit` if a bit outside every field is set.
+            /// - The errors of `try_unpack` otherwise.
+            pub fn unpack_strict(
+                packed: #krate::__private::Vec<u64>,
+            ) -> ::core::result::Result<Self, #krate::UnpackError> {
+                let mut mask = #krate::Bitfield::with_order(#num_bits, #order);
+                if packed.len() != mask.parts.len() {
+                    return ::core::result::Result::Err(#krate::UnpackError::LengthMismatch {
+                        struct_name: ::core::stringify!(#name),
+                        expected: mask.parts.len(),
+                        found: packed.len(),
+                    });
+                }
+                #(#mask_fields)*
+                let stray = #krate::Bitfield::from_parts(
+                    packed
+                        .iter()
+                        .zip(&mask.parts)
+                        .map(|(part, mask)| part & !mask)
+                        .collect(),
+                    #order,
+                );
+                if let ::core::option::Option::Some(bit) = stray.first_set_from(0) {
+                    return ::core::result::Result::Err(#krate::UnpackError::UnexpectedBit {
+                        struct_name: ::core::stringify!(#name),
+                        bit,
+                    });
+                }
+                Self::try_unpack(packed)
+            }
+
+            /// Returns a value whose `Debug` output lists the fields set in `packed`, such as
+            /// `Example { a: true, c: true }`, followed by the indices of the bits set outside
+            /// every field, if any. Integer, enum and nested fields show their raw bits, and
+            /// fields missing from a short vector are left out.
+            pub fn debug_packed(packed: &[u64]) -> impl ::core::fmt::Debug {
+                let bitfield =
+                    #krate::Bitfield::from_parts(#krate::__private::Vec::from(packed), #order);
+                let available_bits = bitfield.parts.len() * 64;
+                let mut stray = bitfield.clone();
+                #(#clear_fields)*
+                #krate::__private::DebugPacked {
+                    struct_name: ::core::stringify!(#name),
+                    fields: [#(#debug_fields),*],
+                    stray_bits: (0..available_bits).filter(move |&bit| stray.get(bit)),
+                }
+            }
+        }
+    }
 }
 
 /// Macro to derive bitwise packing and unpacking methods for a struct with boolean fields.
 ///
+/// Besides the inherent `pack`/`unpack` methods, the derive implements `bitval::BitPack` for the
+/// struct, with `Packed` set to the integer type (or `Vec<u64>`) chosen by `size`.
+///
+/// The derive also emits associated constants describing the layout: `PACKED_BITS`, the number of packed bits,
+/// and `A_BIT`, the index of the first bit of each packed field `a`. For the fixed sizes, `A_MASK` holds the
+/// bits of each field and `ALL_MASK` the bits of every field, typed as the packed integer.
+///
+/// `pack_bytes` and `unpack_bytes` convert the struct to and from `[u8; PACKED_BYTES]`, the fewest bytes that
+/// hold every packed bit, in the byte order given by `endian`.
+///
+/// `try_unpack` unpacks untrusted input without panicking: it returns a `bitval::UnpackError` when the
+/// input is shorter than the struct, sets bits past the last field, or holds an invalid enum discriminant.
+/// The struct converts into its packed value with `From`, by value or by reference, and back with `From`. When
+/// `unpack` could panic, because of an `enum` or `nested` field, reserved bits that must be clear, or in `auto`
+/// mode without `overflow` a vector that is too short, the conversion back is `TryFrom` through `try_unpack`
+/// instead, with `bitval::UnpackError` as its error. In `auto` mode, the struct also converts into a
+/// `bitval::Bitfield` in the chosen bit order.
+///
+/// `unpack_strict` checks the input further, rejecting any set bit outside `ALL_MASK`, such as a bit between two
+/// pinned fields, and in `auto` mode any vector whose length differs from the one `pack` produces.
+///
+/// `debug_packed` takes a packed value (a slice of parts in `auto` mode) and returns something whose `Debug`
+/// output lists the fields it sets, as in `Example { a: true, retries: 5, stray_bits: [12] }`, with the indices of
+/// any bits outside every field. A `packed_type` newtype implements `Debug` the same way.
+///
+/// For the fixed sizes, `pack` is a `const fn` when every packed field is a `bool` or an integer field without
+/// `out_of_range = "error"`, and `unpack` and `try_unpack` are when every packed field is a `bool` or an integer
+/// and the only fields left out are `PhantomData`. The accessors of a `packed_type` follow the same rules, field
+/// by field. Packed values can then be computed in constants, statics and array lengths.
+///
 /// # Attributes
 ///
-/// - `rbitpack(size = "i32", overflow = true)`: Configures the packing options.
-///     - `size`: Specifies the type of integer to use for packing (`"i8"`, `"i16"`, `"i32"`, `"i64"`, or `"auto"`).
+/// - `rbitpack(size = "u32", overflow = true)`: Configures the packing options.
+///     - `size`: Specifies the type of integer to use for packing, from `"u8"` to `"u128"`, or `"auto"`. The signed
+///       types, from `"i8"` to `"i128"`, pack the same bits and reinterpret them as a signed integer. Any other value
+///       is a compile error.
 ///     - `overflow`: A boolean indicating whether to allow packing more boolean fields than the bit capacity of the chosen integer type (default is `false`).
+///       Fields that do not fit are dropped by `pack` and unpacked as `false` (or `0`).
+///     - `out_of_range`: The default policy for integer fields whose value does not fit in their `bits` (see below).
+///     - `reserved`: What `unpack` does with the bits of `reserved` fields (see below): `"ignore"` them (the
+///       default), require them to be `"zero"`, making `unpack` panic and `try_unpack` return
+///       `UnpackError::InvalidValue` otherwise, or `"preserve"` them in the field so they survive a round trip.
+///     - `crate`: The path used by the generated code to reach the `bitval` items, for crates that re-export
+///       them. By default, the derives use `bitwise_packable` when the crate depends on it, and `bitval` otherwise.
+///     - `order`: How bits are numbered: `"lsb0"` (the default) makes bit 0 the least significant bit of the packed
+///       value, and `"msb0"` makes it the most significant one. Field values keep their natural order in both cases,
+///       so in `"msb0"` order the most significant bit of a field comes first. In `"msb0"` order, a field that only
+///       partly fits with `overflow = true` is dropped as a whole.
+///     - `endian`: The byte order of `pack_bytes` and `unpack_bytes`, `"little"` (the default) or `"big"`.
+///     - `strict`: Converts the packed value into the struct with `TryFrom`, through `unpack_strict`, instead of
+///       `From`, through `unpack`, or `TryFrom`, through `try_unpack`.
+///     - `serde`: Derives `Serialize` and `Deserialize`, which need the `serde` feature of `bitval`. `"packed"`
+///       stores the struct as its packed value, read back with `try_unpack` (or `unpack_strict` with `strict`), and
+///       `"names"` as the list of the names of its set fields, which must all be `bool`.
+///     - `packed_type`: The name of a `#[repr(transparent)]` newtype to generate around the packed integer, such
+///       as `"HeaderPacked"`, for fixed sizes only. For each packed field `a`, it offers `a()`, `set_a()` and
+///       `with_a()` to read and write the field's bits in place. It converts to and from the struct with `From`,
+///       or with `TryFrom` back to the struct when the packed integer does.
+///
+/// - `rbitpack(bits = 3, out_of_range = "saturate")` on a `u8`, `u16`, `u32` or `u64` field: Packs the field into
+///   `bits` consecutive bits instead of leaving it out.
+///     - `bits`: The number of bits used by the field, between 1 and the width of its type. A range such as
+///       `bits = 4..8` (or `4..=7`) also pins the field at those bits.
+///     - `out_of_range`: What `pack` does with a value larger than `bits` can hold: `"truncate"` keeps its low bits
+///       (the default), `"saturate"` clamps it to the largest value that fits, and `"error"` panics.
+///
+/// - `rbitpack(enum)` on a field whose type derives `BitEnum`: Packs the enum as its discriminant, in the
+///   number of bits given by `BitEnum::BITS`. Discriminants that do not map to a variant make `unpack` panic,
+///   unless the enum declares a `fallback` variant. `bits` may reserve a fixed number of bits (or a range), up to
+///   64, for the enum, as long as they are enough for its discriminants.
+///
+/// - `rbitpack(nested)` on a field whose type derives `BitwisePackable` with a fixed `size`: Packs the nested
+///   struct inline, in the number of bits given by its `BitPack::BITS`, and rebuilds it through its own `unpack`.
+///   As for enums, `bits` may reserve a fixed number of bits (or a range) for the nested struct.
+///
+/// - `rbitpack(bit = 5)` on any packed field: Pins the first bit of the field at the given index. `bit`, `bits` and
+///   `out_of_range` are errors on fields that are not packed, including the ones marked with `skip`.
+///
+/// - `rbitpack(reserved = 3)` on a `()` field, or on a `u8`, `u16`, `u32` or `u64` field: Reserves the given number
+///   of bits, between 1 and 64, such as a gap in a hardware register. `pack` clears them, unless the struct
+///   preserves them, which needs an integer field to hold them. `packed_type` has no accessors for them.
+///
+/// - `rbitpack(skip, default = "path::to::function")` on any field: Leaves the field out of the packed value.
+///   `unpack` fills it by calling the `default` function, or with `Default::default()` when none is given.
+///   Fields that cannot be packed, such as a `String`, are left out the same way without needing `skip`.
+///
+/// Fields are assigned bits in declaration order, starting from bit 0, each one right after the previous packed
+/// field. Pinning fields with `bit` or a range of `bits` keeps the layout stable when fields are reordered in
+/// source; pinned fields must not overlap any other field, nor go past the bits of the chosen integer, or the
+/// struct fails to compile. Integer fields are unpacked by masking their bits, so they always come back as the
+/// non-negative value that was stored.
+///
+/// Tuple structs are packed the same way, by position, and unit structs pack to zero bits. The items generated
+/// for a positional field are named after its index, as in `FIELD_0_BIT` or `set_field_0()`.
+///
+/// Generic structs keep their parameters, lifetimes and where clauses in the generated impls, and `PhantomData`
+/// fields are left out and rebuilt by `unpack`. Layout checks involving the parameters, such as the width of an
+/// enum field of a generic type, run when the struct is first packed or unpacked with concrete types. Generic
+/// structs cannot have a `packed_type`, and get no byte methods when the width of a field depends on their
+/// parameters.
 ///
 /// # Example
 ///
@@ -63,12 +1202,145 @@ fn get_overflow_type(attrs: &[Attribute]) -> Option<bool> {
 /// use rbitpack::BitwisePackable;
 ///
 /// #[derive(BitwisePackable)]
-/// #[rbitpack(size = "i32", overflow = true)]
+/// #[rbitpack(size = "u32", overflow = true)]
 /// struct MyStruct {
 ///     field1: bool,
 ///     field2: bool,
 ///     field3: bool,
 /// }
+///
+/// #[derive(BitwisePackable)]
+/// #[rbitpack(size = "u16")]
+/// struct Header {
+///     ack: bool,
+///     #[rbitpack(bits = 3)]
+///     mode: u8,
+///     #[rbitpack(bits = 4, out_of_range = "saturate")]
+///     retries: u8,
+/// }
+///
+/// let header = Header { ack: true, mode: 5, retries: 20 };
+/// assert_eq!(header.pack(), 0b1111_101_1);
+/// ```
+///
+/// # Overflow
+///
+/// With `overflow = false`, a struct with more fields than the chosen integer can hold
+/// fails to compile, with the error pointing at the struct and the first field that does not fit.
+/// When enum fields are involved, their width is only known to the compiler, which reports the
+/// overflow through a failed constant assertion instead:
+///
+/// ```compile_fail
+/// use rbitpack::BitwisePackable;
+///
+/// #[derive(BitwisePackable)]
+/// #[rbitpack(size = "u8", overflow = false)]
+/// struct TooManyFlags {
+///     a: bool,
+///     b: bool,
+///     c: bool,
+///     d: bool,
+///     e: bool,
+///     f: bool,
+///     g: bool,
+///     h: bool,
+///     i: bool,
+/// }
+/// ```
+///
+/// Fields pinned onto bits that are already taken are rejected the same way:
+///
+/// ```compile_fail
+/// use rbitpack::BitwisePackable;
+///
+/// #[derive(BitwisePackable)]
+/// #[rbitpack(size = "u8")]
+/// struct Overlapping {
+///     #[rbitpack(bits = 0..4)]
+///     low: u8,
+///     #[rbitpack(bit = 3)]
+///     flag: bool,
+/// }
+/// ```
+///
+/// So is a `size` that does not name a supported integer type:
+///
+/// ```compile_fail
+/// use rbitpack::BitwisePackable;
+///
+/// #[derive(BitwisePackable)]
+/// #[rbitpack(size = "i23")]
+/// struct Odd {
+///     a: bool,
+/// }
+/// ```
+///
+/// Nesting a struct with `size = "auto"` fails through a constant assertion naming the field, as nested structs
+/// need a fixed `size`:
+///
+/// ```compile_fail
+/// use rbitpack::BitwisePackable;
+///
+/// #[derive(BitwisePackable)]
+/// #[rbitpack(size = "auto")]
+/// struct Inner {
+///     a: bool,
+/// }
+///
+/// #[derive(BitwisePackable)]
+/// #[rbitpack(size = "u8")]
+/// struct Outer {
+///     #[rbitpack(nested)]
+///     inner: Inner,
+/// }
+/// ```
+///
+/// Layout attributes on a field that is not packed are rejected too, here `bit` on an integer field without
+/// `bits`:
+///
+/// ```compile_fail
+/// use rbitpack::BitwisePackable;
+///
+/// #[derive(BitwisePackable)]
+/// #[rbitpack(size = "u8")]
+/// struct Unpacked {
+///     a: bool,
+///     #[rbitpack(bit = 3)]
+///     x: u8,
+/// }
+/// ```
+///
+/// Attributes that do not parse, unknown keys and values that are not quoted are errors as well, rather than
+/// being ignored:
+///
+/// ```compile_fail
+/// use rbitpack::BitwisePackable;
+///
+/// #[derive(BitwisePackable)]
+/// #[rbitpack(size = u16)]
+/// struct Unquoted {
+///     a: bool,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use rbitpack::BitwisePackable;
+///
+/// #[derive(BitwisePackable)]
+/// #[rbitpack(size = "u8" overflow = false)]
+/// struct MissingComma {
+///     a: bool,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use rbitpack::BitwisePackable;
+///
+/// #[derive(BitwisePackable)]
+/// #[rbitpack(size = "u16", endain = "big")]
+/// struct Misspelled {
+///     a: bool,
+/// }
 /// ```
 #[proc_macro_derive(BitwisePackable, attributes(rbitpack))]
 pub fn bitwise_packable(input: TokenStream) -> TokenStream {
@@ -79,380 +1351,310 @@ pub fn bitwise_packable(input: TokenStream) -> TokenStream {
         _ => panic!("BitwisePackable can only be used with structs"),
     };
 
-    // Collect boolean fields
-    let fields: Vec<&Field> = data
-        .fields
-        .iter()
-        .filter(
-            |f| matches!(&f.ty, Type::Path(syn::TypePath { path, .. }) if path.is_ident("bool")),
+    if let Err(error) = check_attributes(&input.attrs, &["strict"], STRUCT_KEYS) {
+        return error.to_compile_error().into();
+    }
+    let krate = match get_crate_path(&input.attrs) {
+        Ok(krate) => krate,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    let layout = match collect_fields(data, &input.attrs, &krate) {
+        Ok(layout) => layout,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    let packed_name = match get_packed_type_name(&input.attrs) {
+        Ok(packed_name) => packed_name,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    if let (Some(packed_name), false) = (&packed_name, input.generics.params.is_empty()) {
+        return syn::Error::new_spanned(
+            packed_name,
+            "`packed_type` is not supported on generic structs",
         )
-        .collect();
-
-    let num_fields = fields.len();
-    let field_names: Vec<_> = fields
-        .iter()
-        .map(|f| f.ident.as_ref().unwrap())
-        .collect::<Vec<_>>();
-
-    let fields_idx: Vec<usize> = (0..num_fields).collect();
-
-    let attrs = get_packing_type(&input.attrs);
-    let overflow = get_overflow_type(&input.attrs).unwrap_or(false);
-    let size = attrs.unwrap_or_else(|| "auto".to_string());
-
-    let (pack_code, unpack_code) = match size.as_str() {
-        "i8" => (
-            quote! {
-                impl #name {
-                    /// Packs the boolean fields of the struct into an 8-bit unsigned integer (u8).
-                    /// This method sets each bit in the resulting u8 to represent each boolean field.
-                    /// If the struct has more than 8 boolean fields, and overflow is not allowed, it will panic.
-                    ///
-                    /// # Returns
-                    /// - A `u8` where each bit represents the state of a boolean field in the struct.
-                    pub fn pack(&self) -> u8 {
-                        let mut result = 0u8;
-                        let mut bit_index = 0;
-                        let max_bits = 8;
-
-                        // Single overflow check
-                        if #num_fields > max_bits && !#overflow {
-                            panic!(
-                                "Overflow occurred during packing: struct '{}' has more boolean fields than can be packed in an u8 (8 bits).",
-                                stringify!(#name)
-                            );
-                        }
+        .to_compile_error()
+        .into();
+    }
+    let num_bits = total_bits(&layout.packed);
 
-                        #(
-                            if bit_index < max_bits {
-                                result |= (self.#field_names as u8) << bit_index;
-                                bit_index += 1;
-                            } // No additional else condition needed
-                        )*
-                        result
-                    }
+    let overflow = match get_overflow_type(&input.attrs) {
+        Ok(overflow) => overflow,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    let strict = match has_attribute_flag(&input.attrs, "strict") {
+        Ok(strict) => strict,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    let fixed_width = match get_packing_size(&input.attrs) {
+        Ok(size) => size.map(|(bits, signed)| FixedWidth::new(bits, signed)),
+        Err(error) => return error.to_compile_error().into(),
+    };
 
-                    /// Unpacks an 8-bit unsigned integer (u8) into the boolean fields of the struct.
-                    /// This method reads each bit from the given u8 and assigns it to the corresponding boolean field.
-                    /// If the struct has more than 8 boolean fields, and overflow is not allowed, it will panic.
-                    ///
-                    /// # Parameters
-                    /// - `packed`: A `u8` where each bit represents the state of a boolean field to be unpacked.
-                    ///
-                    /// # Returns
-                    /// - A new instance of the struct with its boolean fields set according to the bits in `packed`.
-                    pub fn unpack(packed: u8) -> Self {
-                        let mut bit_index = 0;
-
-                        // Overflow check
-                        if #num_fields > 8 && !#overflow {
-                            panic!(
-                                "Overflow occurred during unpacking: struct '{}' has more boolean fields than can be unpacked from an u8 (8 bits).",
-                                stringify!(#name)
-                            );
-                        }
+    let mut layout_checks = layout.checks.clone();
+    let mut packed_type = None;
+    let (packed_methods, packed_ty, packed_bits) = match &fixed_width {
+        Some(fixed) => {
+            let max_bits = fixed.bits;
+            let packed_ty = &fixed.output;
+            match pinned_range_checks(name, &layout.packed, fixed) {
+                Ok(checks) => layout_checks.extend(checks),
+                Err(error) => return error.to_compile_error().into(),
+            }
 
-                        #(
-                            let #field_names = if bit_index < 8 {
-                                (packed & (1 << bit_index)) != 0
-                            } else {
-                                false
-                            };
-                            bit_index += 1;
-                        )*
-                        Self {
-                            #(#field_names),*
-                        }
-                    }
+            match num_bits.value() {
+                Some(bits) if bits > max_bits && !overflow => {
+                    return overflow_error(name, &layout.packed, fixed)
+                        .to_compile_error()
+                        .into();
                 }
-            },
-            quote! {},
-        ),
-
-        "i16" => (
-            quote! {
-                impl #name {
-                    /// Packs the boolean fields of the struct into a 16-bit unsigned integer (u16).
-                    /// This method sets each bit in the resulting u16 to represent each boolean field.
-                    /// If the struct has more than 16 boolean fields, and overflow is not allowed, it will panic.
-                    ///
-                    /// # Returns
-                    /// - A `u16` where each bit represents the state of a boolean field in the struct.
-                    pub fn pack(&self) -> u16 {
-                        let mut result = 0u16;
-                        let mut bit_index = 0;
-                        let max_bits = 16;
-
-                        // Single overflow check
-                        if #num_fields > max_bits && !#overflow {
-                            panic!(
-                                "Overflow occurred during packing: struct '{}' has more boolean fields than can be packed in an u16 (16 bits).",
-                                stringify!(#name)
-                            );
-                        }
-
-                        #(
-                            if bit_index < max_bits {
-                                result |= (self.#field_names as u16) << bit_index;
-                                bit_index += 1;
-                            } // No additional else condition needed
-                        )*
-                        result
-                    }
+                // The width of enum fields is only known to the compiler, so check it there.
+                None if !overflow => {
+                    let message = format!(
+                        "struct '{}' has more bits than fit in {}; \
+                         use a larger `size` or set `overflow = true`",
+                        name,
+                        fixed.describe()
+                    );
+                    layout_checks.push(quote! {
+                        ::core::assert!(#num_bits <= #max_bits, #message)
+                    });
+                }
+                _ => {}
+            }
 
-                    /// Unpacks a 16-bit unsigned integer (u16) into the boolean fields of the struct.
-                    /// This method reads each bit from the given u16 and assigns it to the corresponding boolean field.
-                    /// If the struct has more than 16 boolean fields, and overflow is not allowed, it will panic.
-                    ///
-                    /// # Parameters
-                    /// - `packed`: A `u16` where each bit represents the state of a boolean field to be unpacked.
-                    ///
-                    /// # Returns
-                    /// - A new instance of the struct with its boolean fields set according to the bits in `packed`.
-                    pub fn unpack(packed: u16) -> Self {
-                        let mut bit_index = 0;
-
-                        // Overflow check
-                        if #num_fields > 16 && !#overflow {
-                            panic!(
-                                "Overflow occurred during unpacking: struct '{}' has more boolean fields than can be unpacked from an u16 (16 bits).",
-                                stringify!(#name)
-                            );
-                        }
+            let packed_bits = match num_bits.value() {
+                Some(bits) => {
+                    let bits = bits.min(max_bits);
+                    quote!(#bits)
+                }
+                None => quote! {{
+                    let bits = #num_bits;
+                    if bits < #max_bits { bits } else { #max_bits }
+                }},
+            };
 
-                        #(
-                            let #field_names = if bit_index < 16 {
-                                (packed & (1 << bit_index)) != 0
-                            } else {
-                                false
-                            };
-                            bit_index += 1;
-                        )*
-                        Self {
-                            #(#field_names),*
-                        }
-                    }
+            packed_type = packed_name.as_ref().map(|packed_name| {
+                packed_type::expand(
+                    &input,
+                    packed_name,
+                    &layout,
+                    &krate,
+                    overflow,
+                    strict,
+                    fixed,
+                )
+            });
 
-                }
-            },
-            quote! {},
+            (
+                fixed_width_methods(&input, &layout, &krate, overflow, fixed, &packed_bits),
+                packed_ty.clone(),
+                packed_bits,
+            )
+        }
+        None if packed_name.is_some() => {
+            return syn::Error::new_spanned(
+                packed_name,
+                "`packed_type` needs a fixed `size`, such as \"u16\"",
+            )
+            .to_compile_error()
+            .into();
+        }
+        None => (
+            auto_methods(&input, &layout, &krate, overflow),
+            quote!(#krate::__private::Vec<u64>),
+            quote!(#num_bits),
         ),
+    };
 
-        "i32" => (
-            quote! {
-                impl #name {
-                    /// Packs the boolean fields of the struct into a 32-bit unsigned integer (u32).
-                    /// This method sets each bit in the resulting u32 to represent each boolean field.
-                    /// If the struct has more than 32 boolean fields, and overflow is not allowed, it will panic.
-                    ///
-                    /// # Returns
-                    /// - A `u32` where each bit represents the state of a boolean field in the struct.
-                    pub fn pack(&self) -> u32 {
-                        let mut result = 0u32;
-                        let mut bit_index = 0;
-                        let max_bits = 32;
-
-                        // Single overflow check
-                        if #num_fields > max_bits && !#overflow {
-                            panic!(
-                                "Overflow occurred during packing: struct '{}' has more boolean fields than can be packed in an u32 (32 bits).",
-                                stringify!(#name)
-                            );
-                        }
+    let constants = match layout_constants(
+        &input,
+        &layout,
+        fixed_width.as_ref(),
+        overflow,
+        &packed_bits,
+    ) {
+        Ok(constants) => constants,
+        Err(error) => return error.to_compile_error().into(),
+    };
 
-                        #(
-                            if bit_index < max_bits {
-                                result |= (self.#field_names as u32) << bit_index;
-                                bit_index += 1;
-                            } // No additional else condition needed
-                        )*
-                        result
-                    }
+    let endian = match get_endian(&input.attrs) {
+        Ok(endian) => endian,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    // Array lengths cannot depend on generic parameters, so a struct whose layout does has no
+    // byte methods.
+    let byte_methods = (!layout.depends_on(&input.generics)).then(|| {
+        byte_methods(
+            &input,
+            &layout,
+            &krate,
+            fixed_width.as_ref(),
+            endian,
+            &packed_bits,
+        )
+    });
 
-                    /// Unpacks a 32-bit unsigned integer (u32) into the boolean fields of the struct.
-                    /// This method reads each bit from the given u32 and assigns it to the corresponding boolean field.
-                    /// If the struct has more than 32 boolean fields, and overflow is not allowed, it will panic.
-                    ///
-                    /// # Parameters
-                    /// - `packed`: A `u32` where each bit represents the state of a boolean field to be unpacked.
-                    ///
-                    /// # Returns
-                    /// - A new instance of the struct with its boolean fields set according to the bits in `packed`.
-                    pub fn unpack(packed: u32) -> Self {
-                        let mut bit_index = 0;
-
-                        // Overflow check
-                        if #num_fields > 32 && !#overflow {
-                            panic!(
-                                "Overflow occurred during unpacking: struct '{}' has more boolean fields than can be unpacked from an u32 (32 bits).",
-                                stringify!(#name)
-                            );
-                        }
+    let conversions = convert::expand(
+        &input,
+        &layout,
+        &krate,
+        &packed_ty,
+        fixed_width.is_none(),
+        overflow,
+        strict,
+    );
+    let serde_impls = match get_serde_mode(&input.attrs) {
+        Ok(Some(mode)) => {
+            match serde_impl::expand(&input, &layout, &krate, &packed_ty, mode, strict) {
+                Ok(serde_impls) => Some(serde_impls),
+                Err(error) => return error.to_compile_error().into(),
+            }
+        }
+        Ok(None) => None,
+        Err(error) => return error.to_compile_error().into(),
+    };
 
-                        #(
-                            let #field_names = if bit_index < 32 {
-                                (packed & (1 << bit_index)) != 0
-                            } else {
-                                false
-                            };
-                            bit_index += 1;
-                        )*
-                        Self {
-                            #(#field_names),*
-                        }
-                    }
+    let layout_checks = if input.generics.params.is_empty() {
+        quote!(#(const _: () = #layout_checks;)*)
+    } else {
+        let header = impl_header(&input);
+        quote! {
+            #header {
+                const LAYOUT_CHECKS: () = { #(#layout_checks;)* };
+            }
+        }
+    };
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
-                }
-            },
-            quote! {},
-        ),
+    let expanded = quote! {
+        #layout_checks
 
-        "i64" => (
-            quote! {
-                impl #name {
-                    /// Packs the boolean fields of the struct into a 64-bit unsigned integer (u64).
-                    /// This method sets each bit in the resulting u64 to represent each boolean field.
-                    /// If the struct has more than 64 boolean fields, and overflow is not allowed, it will panic.
-                    ///
-                    /// # Returns
-                    /// - A `u64` where each bit represents the state of a boolean field in the struct.
-                    pub fn pack(&self) -> u64 {
-                        let mut result = 0u64;
-                        let mut bit_index = 0;
-                        let max_bits = 64;
-
-                        // Single overflow check
-                        if #num_fields > max_bits && !#overflow {
-                            panic!(
-                                "Overflow occurred during packing: struct '{}' has more boolean fields than can be packed in an u64 (64 bits).",
-                                stringify!(#name)
-                            );
-                        }
+        #constants
 
-                        #(
-                            if bit_index < max_bits {
-                                result |= (self.#field_names as u64) << bit_index;
-                                bit_index += 1;
-                            } // No additional else condition needed
-                        )*
-                        result
-                    }
+        #packed_methods
 
-                    /// Unpacks a 64-bit unsigned integer (u64) into the boolean fields of the struct.
-                    /// This method reads each bit from the given u64 and assigns it to the corresponding boolean field.
-                    /// If the struct has more than 64 boolean fields, and overflow is not allowed, it will panic.
-                    ///
-                    /// # Parameters
-                    /// - `packed`: A `u64` where each bit represents the state of a boolean field to be unpacked.
-                    ///
-                    /// # Returns
-                    /// - A new instance of the struct with its boolean fields set according to the bits in `packed`.
-                    pub fn unpack(packed: u64) -> Self {
-                        let mut bit_index = 0;
-
-                        // Overflow check
-                        if #num_fields > 64 && !#overflow {
-                            panic!(
-                                "Overflow occurred during unpacking: struct '{}' has more boolean fields than can be unpacked from an u64 (64 bits).",
-                                stringify!(#name)
-                            );
-                        }
+        #byte_methods
 
-                        #(
-                            let #field_names = if bit_index < 64 {
-                                (packed & (1 << bit_index)) != 0
-                            } else {
-                                false
-                            };
-                            bit_index += 1;
-                        )*
-                        Self {
-                            #(#field_names),*
-                        }
-                    }
+        #packed_type
 
-                }
-            },
-            quote! {},
-        ),
+        #conversions
 
-        _ => (
-            quote! {
-                impl #name {
-                    /// Packs the boolean fields of the struct into a vector of 64-bit unsigned integers (Vec<u64>).
-                    /// This method sets each bit in the resulting vector to represent each boolean field.
-                    /// The size of the vector is determined by the number of boolean fields divided by 64, rounded up.
-                    /// If overflow is not allowed, it will panic if the struct has more boolean fields than can be packed in the vector.
-                    ///
-                    /// # Returns
-                    /// - A `Vec<u64>` where each bit represents the state of a boolean field in the struct.
-                    pub fn pack(&self) -> Vec<u64> {
-                        let num_fields = #num_fields;
-                        let mut bitfield = Bitfield::new(num_fields);
-
-                        // Single overflow check
-                        if num_fields > bitfield.parts.len() * 64 && !#overflow {
-                            panic!(
-                                "Overflow occurred during packing: struct '{}' has more boolean fields than can be packed in the provided Bitfield size.",
-                                stringify!(#name)
-                            );
-                        }
+        #serde_impls
 
-                        let mut bit_index = 0;
-                        #(
-                            if bit_index < num_fields {
-                                bitfield.set(bit_index, self.#field_names);
-                                bit_index += 1;
-                            }
-                        )*
+        impl #impl_generics #krate::BitPack for #name #ty_generics #where_clause {
+            type Packed = #packed_ty;
 
-                        bitfield.parts
-                    }
+            const BITS: usize = Self::PACKED_BITS;
 
-                    /// Unpacks a vector of 64-bit unsigned integers (Vec<u64>) into the boolean fields of the struct.
-                    /// This method reads each bit from the given vector and assigns it to the corresponding boolean field.
-                    /// If overflow is not allowed, it will panic if the struct has more boolean fields than can be unpacked from the vector.
-                    ///
-                    /// # Parameters
-                    /// - `packed`: A `Vec<u64>` where each bit represents the state of a boolean field to be unpacked.
-                    ///
-                    /// # Returns
-                    /// - A new instance of the struct with its boolean fields set according to the bits in `packed`.
-                    pub fn unpack(packed: Vec<u64>) -> Self {
-                        let num_fields = #num_fields;
-                        let bitfield = Bitfield {
-                            parts: packed,
-                        };
-
-                        // Overflow check
-                        if num_fields > bitfield.parts.len() * 64 && !#overflow {
-                            panic!(
-                                "Overflow occurred during unpacking: struct '{}' has more boolean fields than can be unpacked from the provided Bitfield size.",
-                                stringify!(#name)
-                            );
-                        }
+            fn pack(&self) -> Self::Packed {
+                #name::pack(self)
+            }
 
-                        let mut booleans = vec![false; num_fields];
-                        for i in 0..num_fields {
-                            booleans[i] = bitfield.get(i);
-                        }
+            fn unpack(packed: Self::Packed) -> Self {
+                #name::unpack(packed)
+            }
 
-                        Self {
-                            #(
-                                #field_names: booleans[#fields_idx],
-                            )*
-                        }
-                    }
-                }
-            },
-            quote! {},
-        ),
-    };
+            fn try_unpack(
+                packed: Self::Packed,
+            ) -> ::core::result::Result<Self, #krate::UnpackError> {
+                #name::try_unpack(packed)
+            }
 
-    let expanded = quote! {
-        #pack_code
-        #unpack_code
+            fn unpack_strict(
+                packed: Self::Packed,
+            ) -> ::core::result::Result<Self, #krate::UnpackError> {
+                #name::unpack_strict(packed)
+            }
+        }
     };
 
     TokenStream::from(expanded)
 }
+
+/// Macro to derive `bitval::BitEnum` for a fieldless enum, so it can be used as a field of a
+/// `BitwisePackable` struct marked with `#[rbitpack(enum)]`.
+///
+/// The enum is stored as its discriminant, in the smallest number of bits that can hold the largest
+/// one. Discriminants must be non-negative integer literals, or left implicit.
+///
+/// # Attributes
+///
+/// - `rbitpack(crate = "bitwise_packable")`: The path used to reach the `bitval` items, as for `BitwisePackable`.
+/// - `rbitpack(fallback = "Unknown")`: Maps every discriminant that does not belong to a variant to the
+///   named variant when unpacking. Without it, `unpack` panics on such discriminants.
+///
+/// # Example
+///
+/// ```rust
+/// use rbitpack::{BitEnum, BitwisePackable};
+///
+/// #[derive(BitEnum, Debug, PartialEq)]
+/// enum Mode {
+///     Idle,
+///     Active,
+///     Sleep,
+/// }
+///
+/// #[derive(BitwisePackable)]
+/// #[rbitpack(size = "u8")]
+/// struct Status {
+///     ready: bool,
+///     #[rbitpack(enum)]
+///     mode: Mode,
+/// }
+///
+/// let status = Status { ready: true, mode: Mode::Sleep };
+/// assert_eq!(status.pack(), 0b10_1);
+/// assert_eq!(Status::unpack(0b01_0).mode, Mode::Active);
+/// ```
+#[proc_macro_derive(BitEnum, attributes(rbitpack))]
+pub fn bit_enum(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match bit_enum::expand(&input) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// Macro to derive a set type for a fieldless enum, holding any combination of its variants.
+///
+/// Each variant is stored as one bit, numbered in declaration order from bit 0. The set is backed by the
+/// smallest unsigned integer with a bit per variant, from `u8` to `u64`, or by a `bitval::Bitfield` for enums
+/// with more than 64 variants. It offers `insert`, `remove`, `contains`, `iter`, `len` and `is_empty`, the
+/// `union`, `intersection` and `difference` of two sets (also available as `|`, `&` and `-`), and conversions
+/// to and from the raw bits with `bits`, `from_bits` and `from_bits_truncate`. Its `Debug` output lists the
+/// names of the flags it holds, as in `Permissions {Read, Exec}`.
+///
+/// # Attributes
+///
+/// - `rbitpack(set = "Permissions")`: The name of the generated set type (default is the enum name followed by
+///   `Set`).
+/// - `rbitpack(crate = "bitwise_packable")`: The path used to reach the `bitval` items, as for `BitwisePackable`.
+///
+/// # Example
+///
+/// ```rust
+/// use rbitpack::BitFlags;
+///
+/// #[derive(BitFlags, Debug, PartialEq)]
+/// #[rbitpack(set = "Permissions")]
+/// enum Permission {
+///     Read,
+///     Write,
+///     Exec,
+/// }
+///
+/// let mut permissions = Permissions::from(Permission::Read);
+/// permissions.insert(Permission::Exec);
+/// assert!(permissions.contains(Permission::Exec));
+/// assert_eq!(permissions.bits(), 0b101);
+/// assert_eq!(permissions.iter().collect::<Vec<_>>(), vec![Permission::Read, Permission::Exec]);
+/// assert_eq!(format!("{:?}", permissions), "Permissions {Read, Exec}");
+/// ```
+#[proc_macro_derive(BitFlags, attributes(rbitpack))]
+pub fn bit_flags(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match bit_flags::expand(&input) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
@@ -0,0 +1,147 @@
+use crate::layout::{FieldKind, Layout};
+use crate::{field_bits, pack_value, unpack_value, FieldBits, FixedWidth};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{DeriveInput, Ident, Path};
+
+/// Generates the `#[rbitpack(packed_type = "...")]` newtype: a `#[repr(transparent)]` wrapper
+/// around the packed integer, with accessors reading and writing the bits of each field in place,
+/// conversions to and from the struct, and the `Debug` output of `debug_packed`.
+pub(crate) fn expand(
+    input: &DeriveInput,
+    packed_name: &Ident,
+    layout: &Layout,
+    krate: &Path,
+    overflow: bool,
+    strict: bool,
+    fixed: &FixedWidth,
+) -> TokenStream {
+    let (packed_ty, output_ty, max_bits) = (&fixed.unsigned, &fixed.output, fixed.bits);
+    let name = &input.ident;
+    let vis = &input.vis;
+    let doc = format!(
+        " The fields of [`{}`], packed into {} {}. Each field can be read and written in place, \
+         without unpacking the whole struct.",
+        name,
+        if max_bits == 8 { "an" } else { "a" },
+        output_ty
+    );
+
+    let mut accessors = Vec::new();
+    // Reserved bits are not meant to be read or written on their own.
+    for field in layout
+        .packed
+        .iter()
+        .filter(|field| !matches!(field.kind, FieldKind::Reserved { .. }))
+    {
+        let ident = &field.ident;
+        let setter = format_ident!("set_{}", ident);
+        let with = format_ident!("with_{}", ident);
+        let ty = &field.field.ty;
+        let getter_doc = format!(
+            " Returns the `{}` field, read from the packed bits.",
+            field.name()
+        );
+        let setter_doc = format!(" Sets the `{}` field in the packed bits.", field.name());
+        let with_doc = format!(
+            " Returns a copy of the packed value with the `{}` field set to `value`.",
+            field.name()
+        );
+
+        let empty = unpack_value(name, field, krate, quote!(0), false);
+        let (get, set) = match field_bits(field, layout.order, packed_ty, max_bits, overflow) {
+            // Fields that do not fit, with `overflow = true`, are never stored.
+            None => (empty, quote!(let _ = value;)),
+            Some(FieldBits { mask, shift, guard }) => {
+                let value = pack_value(name, field, krate, quote!(value));
+                let bits = fixed.to_unsigned(quote!(self.0));
+                let get =
+                    unpack_value(name, field, krate, quote!((#bits >> #shift) & #mask), false);
+                let packed = fixed.to_output(quote! {
+                    (#bits & !(#mask << #shift)) | ((((#value) as #packed_ty) & #mask) << #shift)
+                });
+                let set = quote! {
+                    self.0 = #packed;
+                };
+                match guard {
+                    Some(guard) => (
+                        quote!(if #guard { #get } else { #empty }),
+                        quote!(if #guard { #set }),
+                    ),
+                    None => (get, set),
+                }
+            }
+        };
+
+        let get_const = field.const_unpack().then(|| quote!(const));
+        let set_const = field.const_pack().then(|| quote!(const));
+        accessors.push(quote! {
+            #[doc = #getter_doc]
+            pub #get_const fn #ident(&self) -> #ty {
+                #get
+            }
+
+            #[doc = #setter_doc]
+            pub #set_const fn #setter(&mut self, value: #ty) {
+                #set
+            }
+
+            #[doc = #with_doc]
+            pub #set_const fn #with(mut self, value: #ty) -> Self {
+                self.#setter(value);
+                self
+            }
+        });
+    }
+
+    // As for the packed integer, the conversion back is fallible when `unpack` could panic.
+    let from_packed = if strict || layout.unpack_can_fail() {
+        let unpack = if strict {
+            quote!(unpack_strict)
+        } else {
+            quote!(try_unpack)
+        };
+        quote! {
+            impl ::core::convert::TryFrom<#packed_name> for #name {
+                type Error = #krate::UnpackError;
+
+                fn try_from(packed: #packed_name) -> ::core::result::Result<Self, Self::Error> {
+                    #name::#unpack(packed.0)
+                }
+            }
+        }
+    } else {
+        quote! {
+            impl ::core::convert::From<#packed_name> for #name {
+                fn from(packed: #packed_name) -> Self {
+                    #name::unpack(packed.0)
+                }
+            }
+        }
+    };
+
+    quote! {
+        #[doc = #doc]
+        #[repr(transparent)]
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+        #vis struct #packed_name(#vis #output_ty);
+
+        impl #packed_name {
+            #(#accessors)*
+        }
+
+        impl ::core::fmt::Debug for #packed_name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                ::core::fmt::Debug::fmt(&#name::debug_packed(self.0), f)
+            }
+        }
+
+        impl ::core::convert::From<#name> for #packed_name {
+            fn from(value: #name) -> Self {
+                #packed_name(#name::pack(&value))
+            }
+        }
+
+        #from_packed
+    }
+}
@@ -0,0 +1,178 @@
+use crate::attrs::SerdeMode;
+use crate::layout::{FieldKind, Layout};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{DeriveInput, GenericParam, Lifetime, LifetimeDef, Path};
+
+/// Generates the `Serialize` and `Deserialize` impls chosen by `#[rbitpack(serde = "...")]`,
+/// through the `serde` re-exported by `bitval` with its `serde` feature.
+pub(crate) fn expand(
+    input: &DeriveInput,
+    layout: &Layout,
+    krate: &Path,
+    packed_ty: &TokenStream,
+    mode: SerdeMode,
+    strict: bool,
+) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let serde = quote!(#krate::__private::serde);
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    // `Deserialize` borrows from the input for a lifetime of its own.
+    let lifetime = Lifetime::new("'de", Span::call_site());
+    let mut de_generics = input.generics.clone();
+    de_generics.params.insert(
+        0,
+        GenericParam::Lifetime(LifetimeDef::new(lifetime.clone())),
+    );
+    let (de_impl_generics, _, _) = de_generics.split_for_impl();
+
+    let (serialize, deserialize) = match mode {
+        SerdeMode::Packed => packed_bodies(input, &serde, packed_ty, strict),
+        SerdeMode::Names => names_bodies(input, layout, &serde)?,
+    };
+
+    Ok(quote! {
+        impl #impl_generics #serde::Serialize for #name #ty_generics #where_clause {
+            fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+            where
+                S: #serde::Serializer,
+            {
+                #serialize
+            }
+        }
+
+        impl #de_impl_generics #serde::Deserialize<#lifetime> for #name #ty_generics #where_clause {
+            fn deserialize<D>(deserializer: D) -> ::core::result::Result<Self, D::Error>
+            where
+                D: #serde::Deserializer<#lifetime>,
+            {
+                #deserialize
+            }
+        }
+    })
+}
+
+/// The struct is written as its packed value, and read back through `try_unpack`, or
+/// `unpack_strict` with `#[rbitpack(strict)]`, so invalid input is reported as a serde error.
+fn packed_bodies(
+    input: &DeriveInput,
+    serde: &TokenStream,
+    packed_ty: &TokenStream,
+    strict: bool,
+) -> (TokenStream, TokenStream) {
+    let name = &input.ident;
+    let unpack = if strict {
+        quote!(unpack_strict)
+    } else {
+        quote!(try_unpack)
+    };
+
+    let serialize = quote! {
+        #serde::Serialize::serialize(&#name::pack(self), serializer)
+    };
+    let deserialize = quote! {
+        let packed = <#packed_ty as #serde::Deserialize>::deserialize(deserializer)?;
+        #name::#unpack(packed).map_err(<D::Error as #serde::de::Error>::custom)
+    };
+    (serialize, deserialize)
+}
+
+/// The struct is written as the list of the names of its set fields, which must all be `bool`,
+/// and read back from such a list, rejecting unknown names. Skipped fields take their default.
+fn names_bodies(
+    input: &DeriveInput,
+    layout: &Layout,
+    serde: &TokenStream,
+) -> syn::Result<(TokenStream, TokenStream)> {
+    if let Some(field) = layout
+        .packed
+        .iter()
+        .find(|field| !matches!(field.kind, FieldKind::Bool))
+    {
+        return Err(syn::Error::new_spanned(
+            &field.field.ty,
+            "`serde = \"names\"` only supports structs whose packed fields are all `bool`",
+        ));
+    }
+
+    let struct_name = input.ident.to_string();
+    let members: Vec<_> = layout.packed.iter().map(|f| &f.member).collect();
+    let locals: Vec<_> = layout.packed.iter().map(|f| &f.local).collect();
+    let names: Vec<_> = layout.packed.iter().map(|f| f.name()).collect();
+    let count = names.len();
+    let expecting = format!("a list of the fields of struct '{}'", struct_name);
+    let constructor = layout.constructor();
+
+    let serialize = quote! {
+        let len = 0 #(+ self.#members as usize)*;
+        let mut seq = #serde::Serializer::serialize_seq(serializer, ::core::option::Option::Some(len))?;
+        #(
+            if self.#members {
+                #serde::ser::SerializeSeq::serialize_element(&mut seq, #names)?;
+            }
+        )*
+        #serde::ser::SerializeSeq::end(seq)
+    };
+
+    let deserialize = quote! {
+        const NAMES: &[&str] = &[#(#names),*];
+
+        /// The index in `NAMES` of a field name.
+        struct FieldIndex(usize);
+
+        impl<'de> #serde::Deserialize<'de> for FieldIndex {
+            fn deserialize<D>(deserializer: D) -> ::core::result::Result<Self, D::Error>
+            where
+                D: #serde::Deserializer<'de>,
+            {
+                struct NameVisitor;
+
+                impl<'de> #serde::de::Visitor<'de> for NameVisitor {
+                    type Value = FieldIndex;
+
+                    fn expecting(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                        f.write_str("a field name")
+                    }
+
+                    fn visit_str<E>(self, value: &str) -> ::core::result::Result<FieldIndex, E>
+                    where
+                        E: #serde::de::Error,
+                    {
+                        match NAMES.iter().position(|name| *name == value) {
+                            ::core::option::Option::Some(index) => ::core::result::Result::Ok(FieldIndex(index)),
+                            ::core::option::Option::None => ::core::result::Result::Err(E::unknown_variant(value, NAMES)),
+                        }
+                    }
+                }
+
+                #serde::Deserializer::deserialize_str(deserializer, NameVisitor)
+            }
+        }
+
+        struct NamesVisitor;
+
+        impl<'de> #serde::de::Visitor<'de> for NamesVisitor {
+            type Value = [bool; #count];
+
+            fn expecting(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                f.write_str(#expecting)
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> ::core::result::Result<Self::Value, A::Error>
+            where
+                A: #serde::de::SeqAccess<'de>,
+            {
+                let mut set = [false; #count];
+                while let ::core::option::Option::Some(FieldIndex(index)) = #serde::de::SeqAccess::next_element(&mut seq)? {
+                    set[index] = true;
+                }
+                ::core::result::Result::Ok(set)
+            }
+        }
+
+        let [#(#locals),*] = #serde::Deserializer::deserialize_seq(deserializer, NamesVisitor)?;
+        ::core::result::Result::Ok(#constructor)
+    };
+    Ok((serialize, deserialize))
+}
@@ -1,17 +1,17 @@
-#![allow(dead_code)]
+#![allow(dead_code, clippy::bool_assert_comparison)]
 
-extern crate rbitpack;
 extern crate bitval;
+extern crate rbitpack;
 
 #[cfg(test)]
 mod tests {
-    use rbitpack::BitwisePackable;
-    use bitval::Bitfield;
+    use bitval::{BitPack, UnpackError};
+    use rbitpack::{BitEnum, BitFlags, BitwisePackable};
 
     #[test]
     fn test_pack_unpack_u8() {
         #[derive(BitwisePackable)]
-        #[rbitpack(size = "i8")]
+        #[rbitpack(size = "u8")]
         struct Example {
             a: bool,
             b: bool,
@@ -34,7 +34,7 @@ mod tests {
     #[test]
     fn test_pack_unpack_u16() {
         #[derive(BitwisePackable)]
-        #[rbitpack(size = "i16")]
+        #[rbitpack(size = "u16")]
         struct Example {
             a: bool,
             b: bool,
@@ -60,7 +60,7 @@ mod tests {
     #[test]
     fn test_pack_unpack_u32() {
         #[derive(BitwisePackable)]
-        #[rbitpack(size = "i32")]
+        #[rbitpack(size = "u32")]
         struct Example {
             a: bool,
             b: bool,
@@ -90,7 +90,7 @@ mod tests {
     #[test]
     fn test_pack_unpack_u64() {
         #[derive(BitwisePackable)]
-        #[rbitpack(size = "i64")]
+        #[rbitpack(size = "u64")]
         struct Example {
             a: bool,
             b: bool,
@@ -129,12 +129,9 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(
-        expected = "Overflow occurred during packing: struct 'OverflowExample' has more boolean fields than can be packed in an u8 (8 bits)."
-    )]
-    fn test_overflow_packing_u8() {
+    fn test_overflow_allowed_u8() {
         #[derive(BitwisePackable)]
-        #[rbitpack(size = "i8", overflow = false)]
+        #[rbitpack(size = "u8", overflow = true)]
         struct OverflowExample {
             a: bool,
             b: bool,
@@ -156,32 +153,13 @@ mod tests {
             f: true,
             g: false,
             h: true,
-            i: false,
+            i: true,
         };
-        OverflowExample::pack(&example); // This should panic due to overflow
-    }
-
-    #[test]
-    #[should_panic(
-        expected = "Overflow occurred during unpacking: struct 'OverflowExample' has more boolean fields than can be unpacked from an u8 (8 bits)."
-    )]
-    fn test_overflow_unpacking_u8() {
-        #[derive(BitwisePackable)]
-        #[rbitpack(size = "i8", overflow = false)]
-        struct OverflowExample {
-            a: bool,
-            b: bool,
-            c: bool,
-            d: bool,
-            e: bool,
-            f: bool,
-            g: bool,
-            h: bool,
-            i: bool,
-        }
-
-        let packed = 0b11111111; // 255 in decimal
-        OverflowExample::unpack(packed); // This should panic due to overflow
+        let packed = OverflowExample::pack(&example);
+        assert_eq!(packed, 0b10101101); // `i` does not fit and is dropped
+        let unpacked = OverflowExample::unpack(0b11111111);
+        assert_eq!(unpacked.h, true);
+        assert_eq!(unpacked.i, false);
     }
 
     #[test]
@@ -211,4 +189,1739 @@ mod tests {
         assert_eq!(unpacked.d, true);
         assert_eq!(unpacked.e, false);
     }
+
+    #[test]
+    fn test_bitpack_trait() {
+        fn round_trip<T: BitPack>(value: &T) -> T {
+            T::unpack(value.pack())
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u16")]
+        struct Fixed {
+            a: bool,
+            b: bool,
+            c: bool,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto")]
+        struct Auto {
+            a: bool,
+            b: bool,
+        }
+
+        assert_eq!(<Fixed as BitPack>::BITS, 3);
+        assert_eq!(<Auto as BitPack>::BITS, 2);
+
+        let fixed: u16 = BitPack::pack(&Fixed {
+            a: false,
+            b: true,
+            c: true,
+        });
+        assert_eq!(fixed, 0b110);
+
+        let unpacked = round_trip(&Fixed {
+            a: true,
+            b: false,
+            c: true,
+        });
+        assert_eq!(unpacked.a, true);
+        assert_eq!(unpacked.b, false);
+        assert_eq!(unpacked.c, true);
+
+        let unpacked = round_trip(&Auto { a: false, b: true });
+        assert_eq!(unpacked.a, false);
+        assert_eq!(unpacked.b, true);
+    }
+
+    #[test]
+    fn test_pack_unpack_multi_bit_fields() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u16")]
+        struct Header {
+            ack: bool,
+            #[rbitpack(bits = 3)]
+            mode: u8,
+            #[rbitpack(bits = 4)]
+            retries: u32,
+            fin: bool,
+        }
+
+        let header = Header {
+            ack: true,
+            mode: 0b101,
+            retries: 9,
+            fin: true,
+        };
+        let packed = Header::pack(&header);
+        assert_eq!(packed, 0b110011011); // fin, retries, mode, ack
+        let unpacked = Header::unpack(packed);
+        assert_eq!(unpacked.ack, true);
+        assert_eq!(unpacked.mode, 0b101);
+        assert_eq!(unpacked.retries, 9);
+        assert_eq!(unpacked.fin, true);
+        assert_eq!(<Header as BitPack>::BITS, 9);
+
+        // Unpacking masks each field, so the values are never sign-extended.
+        let unpacked = Header::unpack(u16::MAX);
+        assert_eq!(unpacked.mode, 0b111);
+        assert_eq!(unpacked.retries, 0b1111);
+    }
+
+    #[test]
+    fn test_multi_bit_out_of_range_policies() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8")]
+        struct Counters {
+            #[rbitpack(bits = 3)]
+            truncated: u8,
+            #[rbitpack(bits = 3, out_of_range = "saturate")]
+            saturated: u16,
+        }
+
+        let counters = Counters {
+            truncated: 0b1101,
+            saturated: 300,
+        };
+        let unpacked = Counters::unpack(Counters::pack(&counters));
+        assert_eq!(unpacked.truncated, 0b101);
+        assert_eq!(unpacked.saturated, 0b111);
+    }
+
+    #[test]
+    #[should_panic(expected = "Value 8 of field 'mode' in struct 'Strict' does not fit in 3 bits.")]
+    fn test_multi_bit_out_of_range_error() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8", out_of_range = "error")]
+        struct Strict {
+            #[rbitpack(bits = 3)]
+            mode: u8,
+        }
+
+        Strict::pack(&Strict { mode: 8 });
+    }
+
+    #[test]
+    fn test_pack_unpack_multi_bit_auto() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto")]
+        struct Wide {
+            a: bool,
+            #[rbitpack(bits = 64)]
+            id: u64,
+            #[rbitpack(bits = 5)]
+            kind: u8,
+        }
+
+        let wide = Wide {
+            a: true,
+            id: u64::MAX - 1,
+            kind: 17,
+        };
+        let packed = Wide::pack(&wide);
+        assert_eq!(packed.len(), 2);
+        let unpacked = Wide::unpack(packed);
+        assert_eq!(unpacked.a, true);
+        assert_eq!(unpacked.id, u64::MAX - 1);
+        assert_eq!(unpacked.kind, 17);
+    }
+
+    #[test]
+    fn test_fields_named_like_generated_locals() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u16")]
+        struct Fixed {
+            packed: bool,
+            #[rbitpack(bits = 4)]
+            bitfield: u8,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto")]
+        struct Auto {
+            bitfield: bool,
+            #[rbitpack(bits = 4)]
+            packed: u8,
+        }
+
+        let unpacked = Fixed::unpack(Fixed::pack(&Fixed {
+            packed: true,
+            bitfield: 9,
+        }));
+        assert_eq!(unpacked.packed, true);
+        assert_eq!(unpacked.bitfield, 9);
+
+        let unpacked = Auto::unpack(Auto::pack(&Auto {
+            bitfield: true,
+            packed: 9,
+        }));
+        assert_eq!(unpacked.bitfield, true);
+        assert_eq!(unpacked.packed, 9);
+    }
+
+    #[derive(BitEnum, Debug, PartialEq)]
+    enum Mode {
+        Idle,
+        Active,
+        Sleep,
+    }
+
+    #[derive(BitEnum, Debug, PartialEq)]
+    #[rbitpack(fallback = "Unknown")]
+    enum Level {
+        Low = 1,
+        High = 4,
+        Unknown = 7,
+    }
+
+    #[test]
+    fn test_bit_enum_derive() {
+        use bitval::BitEnum;
+
+        assert_eq!(<Mode as BitEnum>::BITS, 2);
+        assert_eq!(<Level as BitEnum>::BITS, 3);
+        assert_eq!(Mode::Sleep.to_bits(), 2);
+        assert_eq!(Level::High.to_bits(), 4);
+        assert_eq!(Mode::from_bits(1), Some(Mode::Active));
+        assert_eq!(Mode::from_bits(3), None);
+        assert_eq!(Level::from_bits(2), Some(Level::Unknown));
+    }
+
+    #[test]
+    fn test_pack_unpack_enum_fields() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8")]
+        struct Status {
+            ready: bool,
+            #[rbitpack(enum)]
+            mode: Mode,
+            #[rbitpack(enum)]
+            level: Level,
+            done: bool,
+        }
+
+        let status = Status {
+            ready: true,
+            mode: Mode::Sleep,
+            level: Level::High,
+            done: true,
+        };
+        let packed = Status::pack(&status);
+        assert_eq!(packed, 0b1100101); // done, level, mode, ready
+        assert_eq!(<Status as BitPack>::BITS, 7);
+        let unpacked = Status::unpack(packed);
+        assert_eq!(unpacked.ready, true);
+        assert_eq!(unpacked.mode, Mode::Sleep);
+        assert_eq!(unpacked.level, Level::High);
+        assert_eq!(unpacked.done, true);
+
+        // Unknown discriminants are mapped to the fallback variant.
+        assert_eq!(Status::unpack(0b0010000).level, Level::Unknown);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid value 3 for field 'mode' in struct 'Status'.")]
+    fn test_unpack_unknown_enum_discriminant() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8")]
+        struct Status {
+            #[rbitpack(enum)]
+            mode: Mode,
+        }
+
+        Status::unpack(0b11);
+    }
+
+    #[test]
+    fn test_pack_unpack_wide_enum_fields() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u128")]
+        struct Wide {
+            #[rbitpack(enum, bits = 64)]
+            low: Mode,
+            #[rbitpack(enum, bits = 64)]
+            high: Level,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u128", order = "msb0")]
+        struct WideMsb {
+            #[rbitpack(enum, bits = 64)]
+            low: Mode,
+            #[rbitpack(enum, bits = 64)]
+            high: Level,
+        }
+
+        let packed = Wide {
+            low: Mode::Sleep,
+            high: Level::High,
+        }
+        .pack();
+        assert_eq!(packed, 4 << 64 | 2);
+        let unpacked = Wide::unpack(packed);
+        assert_eq!((unpacked.low, unpacked.high), (Mode::Sleep, Level::High));
+
+        let packed = WideMsb {
+            low: Mode::Active,
+            high: Level::Low,
+        }
+        .pack();
+        assert_eq!(packed, 1 << 64 | 1);
+        let unpacked = WideMsb::unpack(packed);
+        assert_eq!((unpacked.low, unpacked.high), (Mode::Active, Level::Low));
+    }
+
+    #[test]
+    fn test_pack_unpack_enum_fields_auto() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto")]
+        struct Status {
+            #[rbitpack(enum)]
+            mode: Mode,
+            ready: bool,
+        }
+
+        let packed = Status::pack(&Status {
+            mode: Mode::Active,
+            ready: true,
+        });
+        assert_eq!(packed, vec![0b1_01]);
+        let unpacked = Status::unpack(packed);
+        assert_eq!(unpacked.mode, Mode::Active);
+        assert_eq!(unpacked.ready, true);
+    }
+
+    fn default_retries() -> u32 {
+        3
+    }
+
+    #[test]
+    fn test_pack_unpack_mixed_fields() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8")]
+        struct Device {
+            name: String,
+            enabled: bool,
+            #[rbitpack(skip)]
+            dirty: bool,
+            #[rbitpack(skip, default = "default_retries")]
+            retries: u32,
+            visible: bool,
+        }
+
+        let device = Device {
+            name: "sensor".to_string(),
+            enabled: true,
+            dirty: true,
+            retries: 10,
+            visible: true,
+        };
+        let packed = Device::pack(&device);
+        assert_eq!(packed, 0b11);
+        assert_eq!(<Device as BitPack>::BITS, 2);
+
+        let unpacked = Device::unpack(packed);
+        assert_eq!(unpacked.name, "");
+        assert_eq!(unpacked.enabled, true);
+        assert_eq!(unpacked.dirty, false);
+        assert_eq!(unpacked.retries, 3);
+        assert_eq!(unpacked.visible, true);
+    }
+
+    #[test]
+    fn test_generated_code_is_hygienic() {
+        // Local items with the same names as the ones used by the generated code must not be
+        // picked up instead of them.
+        struct Bitfield;
+        #[allow(unused_macros)]
+        macro_rules! panic {
+            ($($arg:tt)*) => {};
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto")]
+        struct Example {
+            a: bool,
+            b: bool,
+        }
+
+        // Nor may fields named like the locals of the generated code shadow them.
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto", overflow = true)]
+        struct Locals {
+            bitfield: bool,
+            packed: bool,
+            available_bits: bool,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8", overflow = true)]
+        struct FixedLocals {
+            packed: bool,
+            result: bool,
+        }
+
+        let _ = Bitfield;
+        let unpacked = Example::unpack(Example::pack(&Example { a: true, b: false }));
+        assert_eq!(unpacked.a, true);
+        assert_eq!(unpacked.b, false);
+
+        let locals = Locals {
+            bitfield: true,
+            packed: false,
+            available_bits: true,
+        };
+        let unpacked = Locals::try_unpack(Locals::pack(&locals)).unwrap();
+        assert_eq!(unpacked.bitfield, true);
+        assert_eq!(unpacked.packed, false);
+        assert_eq!(unpacked.available_bits, true);
+
+        let unpacked = FixedLocals::unpack(FixedLocals::pack(&FixedLocals {
+            packed: true,
+            result: true,
+        }));
+        assert_eq!(unpacked.packed, true);
+        assert_eq!(unpacked.result, true);
+    }
+
+    #[test]
+    fn test_pack_unpack_pinned_positions() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u16")]
+        struct Register {
+            #[rbitpack(bit = 15)]
+            enable: bool,
+            #[rbitpack(bits = 4..8)]
+            prescaler: u8,
+            // Placed right after `prescaler`
+            interrupt: bool,
+            #[rbitpack(bits = 0..=1)]
+            channel: u8,
+            #[rbitpack(enum, bits = 10..12)]
+            mode: Mode,
+        }
+
+        let register = Register {
+            enable: true,
+            prescaler: 0b1010,
+            interrupt: true,
+            channel: 0b11,
+            mode: Mode::Sleep,
+        };
+        let packed = Register::pack(&register);
+        assert_eq!(packed, 0b1000_1001_1010_0011);
+        assert_eq!(<Register as BitPack>::BITS, 16);
+
+        let unpacked = Register::unpack(packed);
+        assert_eq!(unpacked.enable, true);
+        assert_eq!(unpacked.prescaler, 0b1010);
+        assert_eq!(unpacked.interrupt, true);
+        assert_eq!(unpacked.channel, 0b11);
+        assert_eq!(unpacked.mode, Mode::Sleep);
+    }
+
+    #[test]
+    fn test_pack_unpack_pinned_after_enum_auto() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto")]
+        struct Frame {
+            #[rbitpack(enum)]
+            mode: Mode,
+            #[rbitpack(bit = 70)]
+            last: bool,
+            next: bool,
+        }
+
+        let packed = Frame::pack(&Frame {
+            mode: Mode::Active,
+            last: true,
+            next: true,
+        });
+        assert_eq!(packed, vec![0b01, 0b1100_0000]);
+        assert_eq!(<Frame as BitPack>::BITS, 72);
+
+        let unpacked = Frame::unpack(packed);
+        assert_eq!(unpacked.mode, Mode::Active);
+        assert_eq!(unpacked.last, true);
+        assert_eq!(unpacked.next, true);
+    }
+
+    #[test]
+    fn test_try_unpack() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8")]
+        struct Status {
+            ready: bool,
+            #[rbitpack(enum)]
+            mode: Mode,
+        }
+
+        let unpacked = Status::try_unpack(0b101).unwrap();
+        assert_eq!(unpacked.ready, true);
+        assert_eq!(unpacked.mode, Mode::Sleep);
+
+        assert_eq!(
+            Status::try_unpack(0b110).err(),
+            Some(UnpackError::InvalidValue {
+                struct_name: "Status",
+                field: "mode",
+                bit: 1,
+                value: 3,
+            })
+        );
+        assert_eq!(
+            Status::try_unpack(0b0100_0000).err(),
+            Some(UnpackError::UnexpectedBit {
+                struct_name: "Status",
+                bit: 6,
+            })
+        );
+        assert_eq!(
+            <Status as BitPack>::try_unpack(0b1000).err(),
+            Some(UnpackError::UnexpectedBit {
+                struct_name: "Status",
+                bit: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn test_try_unpack_auto() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto")]
+        struct Frame {
+            #[rbitpack(bit = 70)]
+            last: bool,
+        }
+
+        assert_eq!(Frame::try_unpack(vec![0, 0b100_0000]).unwrap().last, true);
+        assert_eq!(
+            Frame::try_unpack(vec![0]).err(),
+            Some(UnpackError::TooShort {
+                struct_name: "Frame",
+                bits: 71,
+                available: 64,
+            })
+        );
+        assert_eq!(
+            Frame::try_unpack(vec![0, 0, 1]).err(),
+            Some(UnpackError::UnexpectedBit {
+                struct_name: "Frame",
+                bit: 128,
+            })
+        );
+    }
+
+    #[test]
+    fn test_packed_type_accessors() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u16", packed_type = "HeaderPacked")]
+        struct Header {
+            ack: bool,
+            #[rbitpack(bits = 3)]
+            retries: u8,
+            #[rbitpack(enum)]
+            mode: Mode,
+            #[rbitpack(bits = 12..14, out_of_range = "saturate")]
+            level: u8,
+        }
+
+        assert_eq!(std::mem::size_of::<HeaderPacked>(), 2);
+
+        let mut packed = HeaderPacked::default().with_ack(true).with_retries(5);
+        packed.set_mode(Mode::Sleep);
+        // mode = 0b10, retries = 0b101, ack = 1
+        assert_eq!(packed.0, 0b0010_1011);
+        assert_eq!(packed.ack(), true);
+        assert_eq!(packed.retries(), 5);
+        assert_eq!(packed.mode(), Mode::Sleep);
+
+        // Setting a field only touches its own bits.
+        packed.set_retries(2);
+        packed.set_level(255);
+        assert_eq!(packed.0, 0b0011_0000_0010_0101);
+        assert_eq!(packed.level(), 3);
+
+        // The enum field makes the conversion back fallible.
+        let header = Header::try_from(packed).unwrap();
+        assert_eq!(header.retries, 2);
+        assert_eq!(header.mode, Mode::Sleep);
+        assert_eq!(HeaderPacked::from(header), packed);
+    }
+
+    #[test]
+    fn test_layout_constants() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u16")]
+        struct Header {
+            ack: bool,
+            #[rbitpack(enum)]
+            mode: Mode,
+            #[rbitpack(bits = 3)]
+            retries: u8,
+            #[rbitpack(bit = 15)]
+            enable: bool,
+        }
+
+        assert_eq!(Header::ACK_BIT, 0);
+        assert_eq!(Header::MODE_BIT, 1);
+        assert_eq!(Header::RETRIES_BIT, 3);
+        assert_eq!(Header::ENABLE_BIT, 15);
+
+        let ack_mask: u16 = Header::ACK_MASK;
+        assert_eq!(ack_mask, 0b1);
+        assert_eq!(Header::MODE_MASK, 0b110);
+        assert_eq!(Header::RETRIES_MASK, 0b11_1000);
+        assert_eq!(Header::ENABLE_MASK, 1 << 15);
+        assert_eq!(Header::ALL_MASK, 0b1000_0000_0011_1111);
+        assert_eq!(Header::PACKED_BITS, 16);
+
+        let header = Header {
+            ack: false,
+            mode: Mode::Idle,
+            retries: 5,
+            enable: true,
+        };
+        assert_eq!(
+            header.pack() & Header::RETRIES_MASK,
+            5 << Header::RETRIES_BIT
+        );
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto")]
+        struct Frame {
+            ready: bool,
+            #[rbitpack(bit = 70)]
+            last: bool,
+        }
+
+        assert_eq!(Frame::READY_BIT, 0);
+        assert_eq!(Frame::LAST_BIT, 70);
+        assert_eq!(Frame::PACKED_BITS, 71);
+    }
+
+    #[test]
+    fn test_pack_unpack_msb0() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u16", order = "msb0")]
+        struct Header {
+            ack: bool,
+            #[rbitpack(bits = 3)]
+            retries: u8,
+            #[rbitpack(enum)]
+            mode: Mode,
+            #[rbitpack(bit = 15)]
+            last: bool,
+        }
+
+        let header = Header {
+            ack: true,
+            retries: 0b011,
+            mode: Mode::Sleep,
+            last: true,
+        };
+        // Bit 0 is the most significant bit, and values keep their natural order.
+        let packed = header.pack();
+        assert_eq!(packed, 0b1011_1000_0000_0001);
+        assert_eq!(Header::RETRIES_BIT, 1);
+        assert_eq!(Header::RETRIES_MASK, 0b0111_0000_0000_0000);
+        assert_eq!(Header::MODE_MASK, 0b0000_1100_0000_0000);
+
+        let unpacked = Header::unpack(packed);
+        assert_eq!(unpacked.ack, true);
+        assert_eq!(unpacked.retries, 0b011);
+        assert_eq!(unpacked.mode, Mode::Sleep);
+        assert_eq!(unpacked.last, true);
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8", order = "msb0")]
+        struct Flags {
+            a: bool,
+            #[rbitpack(bits = 2)]
+            b: u8,
+        }
+
+        assert_eq!(Flags { a: true, b: 1 }.pack(), 0b1010_0000);
+        assert_eq!(
+            Flags::try_unpack(0b1010_0100).err(),
+            Some(UnpackError::UnexpectedBit {
+                struct_name: "Flags",
+                bit: 5,
+            })
+        );
+    }
+
+    #[test]
+    fn test_bit_orders_round_trip() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u32")]
+        struct Lsb0 {
+            #[rbitpack(bits = 5)]
+            a: u8,
+            #[rbitpack(enum)]
+            mode: Mode,
+            #[rbitpack(bits = 20..31)]
+            b: u16,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u32", order = "msb0")]
+        struct Msb0 {
+            #[rbitpack(bits = 5)]
+            a: u8,
+            #[rbitpack(enum)]
+            mode: Mode,
+            #[rbitpack(bits = 20..31)]
+            b: u16,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto", order = "msb0")]
+        struct Msb0Auto {
+            #[rbitpack(bits = 5)]
+            a: u8,
+            #[rbitpack(enum)]
+            mode: Mode,
+            #[rbitpack(bits = 60..71)]
+            b: u16,
+        }
+
+        for (a, b) in [(0b10110, 0b101_0011_1100), (31, 1), (0, 0)] {
+            let lsb0 = Lsb0::unpack(
+                Lsb0 {
+                    a,
+                    mode: Mode::Active,
+                    b,
+                }
+                .pack(),
+            );
+            assert_eq!((lsb0.a, lsb0.mode, lsb0.b), (a, Mode::Active, b));
+
+            let msb0 = Msb0::unpack(
+                Msb0 {
+                    a,
+                    mode: Mode::Sleep,
+                    b,
+                }
+                .pack(),
+            );
+            assert_eq!((msb0.a, msb0.mode, msb0.b), (a, Mode::Sleep, b));
+
+            let auto = Msb0Auto::unpack(
+                Msb0Auto {
+                    a,
+                    mode: Mode::Idle,
+                    b,
+                }
+                .pack(),
+            );
+            assert_eq!((auto.a, auto.mode, auto.b), (a, Mode::Idle, b));
+        }
+
+        // Field positions are mirrored between the two orders, while values keep their
+        // natural order: the top bit of `b` is logical bit 30 in lsb0, and logical bit 20 in msb0.
+        let lsb0 = Lsb0 {
+            a: 0,
+            mode: Mode::Idle,
+            b: 1 << 10,
+        }
+        .pack();
+        let msb0 = Msb0 {
+            a: 0,
+            mode: Mode::Idle,
+            b: 1 << 10,
+        }
+        .pack();
+        assert_eq!(lsb0, 1 << 30);
+        assert_eq!(msb0, 1 << 11);
+    }
+
+    #[test]
+    fn test_pack_unpack_tuple_struct() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8")]
+        struct Flags(bool, bool, bool);
+
+        let packed = Flags(true, false, true).pack();
+        assert_eq!(packed, 0b101);
+        let unpacked = Flags::unpack(packed);
+        assert_eq!(unpacked.0, true);
+        assert_eq!(unpacked.1, false);
+        assert_eq!(unpacked.2, true);
+        assert_eq!(Flags::FIELD_2_BIT, 2);
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u16", packed_type = "RecordPacked")]
+        struct Record(
+            #[rbitpack(bits = 4)] u8,
+            String,
+            #[rbitpack(enum)] Mode,
+            #[rbitpack(skip, default = "default_retries")] u32,
+        );
+
+        let packed = Record(9, "ignored".to_string(), Mode::Sleep, 0).pack();
+        assert_eq!(packed, 0b10_1001);
+        let unpacked = Record::unpack(packed);
+        assert_eq!(unpacked.0, 9);
+        assert_eq!(unpacked.1, "");
+        assert_eq!(unpacked.2, Mode::Sleep);
+        assert_eq!(unpacked.3, 3);
+        assert_eq!(RecordPacked(packed).field_2(), Mode::Sleep);
+        assert_eq!(RecordPacked(packed).with_field_0(1).0, 0b10_0001);
+    }
+
+    #[test]
+    fn test_pack_unpack_unit_struct() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8")]
+        struct Empty;
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto")]
+        struct EmptyAuto;
+
+        assert_eq!(Empty.pack(), 0);
+        assert_eq!(Empty::PACKED_BITS, 0);
+        assert!(matches!(Empty::unpack(0), Empty));
+        assert!(Empty::try_unpack(1).is_err());
+
+        assert_eq!(EmptyAuto.pack(), Vec::<u64>::new());
+        assert!(matches!(EmptyAuto::unpack(vec![]), EmptyAuto));
+    }
+
+    #[derive(BitFlags, Debug, PartialEq)]
+    #[rbitpack(set = "Permissions")]
+    enum Permission {
+        Read,
+        Write,
+        Exec,
+    }
+
+    #[test]
+    fn test_bit_flags() {
+        let mut permissions = Permissions::empty();
+        assert!(permissions.is_empty());
+        assert_eq!(permissions.insert(Permission::Read), true);
+        assert_eq!(permissions.insert(Permission::Read), false);
+        permissions.insert(Permission::Exec);
+
+        assert_eq!(permissions.bits(), 0b101);
+        assert_eq!(permissions.len(), 2);
+        assert!(permissions.contains(Permission::Exec));
+        assert!(!permissions.contains(Permission::Write));
+        assert_eq!(
+            permissions.iter().collect::<Vec<_>>(),
+            vec![Permission::Read, Permission::Exec]
+        );
+
+        assert_eq!(permissions.remove(Permission::Read), true);
+        assert_eq!(permissions.remove(Permission::Read), false);
+        assert_eq!(u8::from(permissions), 0b100);
+
+        let write = Permissions::from(Permission::Write);
+        assert_eq!((permissions | write).bits(), 0b110);
+        assert_eq!(permissions.union(&write).bits(), 0b110);
+        assert_eq!(Permissions::all().intersection(&write).bits(), 0b010);
+        assert_eq!((Permissions::all() - write).bits(), 0b101);
+        assert_eq!((Permissions::all() & write).bits(), 0b010);
+
+        assert_eq!(
+            Permissions::from_bits(0b011),
+            Some([Permission::Read, Permission::Write].into_iter().collect())
+        );
+        assert!(Permissions::from_bits(0b1000).is_none());
+        assert_eq!(Permissions::from_bits_truncate(0b1111).bits(), 0b111);
+        assert_eq!(Permissions::default(), Permissions::empty());
+
+        assert_eq!(format!("{:?}", write), "Permissions {Write}");
+        assert_eq!(
+            format!("{:?}", Permissions::all()),
+            "Permissions {Read, Write, Exec}"
+        );
+        assert_eq!(format!("{:?}", Permissions::empty()), "Permissions {}");
+    }
+
+    #[test]
+    fn test_bit_flags_large_enum() {
+        #[derive(BitFlags, Debug, PartialEq)]
+        enum Channel {
+            V0,
+            V1,
+            V2,
+            V3,
+            V4,
+            V5,
+            V6,
+            V7,
+            V8,
+            V9,
+            V10,
+            V11,
+            V12,
+            V13,
+            V14,
+            V15,
+            V16,
+            V17,
+            V18,
+            V19,
+            V20,
+            V21,
+            V22,
+            V23,
+            V24,
+            V25,
+            V26,
+            V27,
+            V28,
+            V29,
+            V30,
+            V31,
+            V32,
+            V33,
+            V34,
+            V35,
+            V36,
+            V37,
+            V38,
+            V39,
+            V40,
+            V41,
+            V42,
+            V43,
+            V44,
+            V45,
+            V46,
+            V47,
+            V48,
+            V49,
+            V50,
+            V51,
+            V52,
+            V53,
+            V54,
+            V55,
+            V56,
+            V57,
+            V58,
+            V59,
+            V60,
+            V61,
+            V62,
+            V63,
+            V64,
+            V65,
+            V66,
+            V67,
+            V68,
+            V69,
+        }
+
+        let mut channels = ChannelSet::empty();
+        channels.insert(Channel::V1);
+        channels.insert(Channel::V69);
+        assert_eq!(channels.bits(), vec![0b10, 1 << 5]);
+        assert_eq!(channels.len(), 2);
+        assert!(channels.contains(Channel::V69));
+        assert_eq!(
+            channels.iter().collect::<Vec<_>>(),
+            vec![Channel::V1, Channel::V69]
+        );
+
+        assert_eq!(ChannelSet::all().len(), 70);
+        assert_eq!(ChannelSet::all().bits(), vec![u64::MAX, 0b11_1111]);
+        assert_eq!((ChannelSet::all() - channels.clone()).len(), 68);
+        assert_eq!(
+            ChannelSet::from_bits(vec![0b10, 1 << 5]),
+            Some(channels.clone())
+        );
+        assert_eq!(format!("{:?}", channels), "ChannelSet {V1, V69}");
+        assert!(ChannelSet::from_bits(vec![0, 1 << 6]).is_none());
+        assert_eq!(
+            ChannelSet::from_bits_truncate(vec![0, u64::MAX, 1]).bits(),
+            vec![0, 0b11_1111]
+        );
+        assert_eq!(Vec::<u64>::from(channels), vec![0b10, 1 << 5]);
+    }
+
+    #[derive(BitwisePackable, Debug, PartialEq)]
+    #[rbitpack(size = "u8")]
+    struct NetworkFlags {
+        online: bool,
+        #[rbitpack(enum)]
+        mode: Mode,
+    }
+
+    #[test]
+    fn test_pack_unpack_nested_struct() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u16", packed_type = "DeviceFlagsPacked")]
+        struct DeviceFlags {
+            powered: bool,
+            #[rbitpack(nested)]
+            network: NetworkFlags,
+            #[rbitpack(bits = 4)]
+            retries: u8,
+        }
+
+        let device = DeviceFlags {
+            powered: true,
+            network: NetworkFlags {
+                online: true,
+                mode: Mode::Sleep,
+            },
+            retries: 9,
+        };
+        let packed = device.pack();
+        // retries = 0b1001, network = 0b10_1, powered = 1
+        assert_eq!(packed, 0b1001_1011);
+        assert_eq!(<DeviceFlags as BitPack>::BITS, 8);
+        assert_eq!(DeviceFlags::RETRIES_BIT, 4);
+
+        let unpacked = DeviceFlags::unpack(packed);
+        assert_eq!(unpacked.powered, true);
+        assert_eq!(
+            unpacked.network,
+            NetworkFlags {
+                online: true,
+                mode: Mode::Sleep
+            }
+        );
+        assert_eq!(unpacked.retries, 9);
+
+        assert_eq!(
+            DeviceFlags::try_unpack(0b0000_1100).err(),
+            Some(UnpackError::InvalidValue {
+                struct_name: "NetworkFlags",
+                field: "mode",
+                bit: 1,
+                value: 3,
+            })
+        );
+
+        let packed = DeviceFlagsPacked(packed).with_network(NetworkFlags {
+            online: false,
+            mode: Mode::Active,
+        });
+        assert_eq!(packed.0, 0b1001_0101);
+        assert_eq!(packed.network().mode, Mode::Active);
+    }
+
+    #[test]
+    fn test_pack_unpack_nested_struct_auto() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto")]
+        struct Device {
+            #[rbitpack(bits = 62)]
+            id: u64,
+            #[rbitpack(nested, bits = 4)]
+            network: NetworkFlags,
+        }
+
+        let packed = Device {
+            id: 1,
+            network: NetworkFlags {
+                online: true,
+                mode: Mode::Active,
+            },
+        }
+        .pack();
+        assert_eq!(packed, vec![0b11 << 62 | 1, 0]);
+        assert_eq!(<Device as BitPack>::BITS, 66);
+
+        let unpacked = Device::unpack(packed);
+        assert_eq!(unpacked.id, 1);
+        assert_eq!(unpacked.network.online, true);
+        assert_eq!(unpacked.network.mode, Mode::Active);
+    }
+
+    #[test]
+    fn test_pack_unpack_u128() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u128")]
+        struct Wide {
+            #[rbitpack(bits = 64)]
+            low: u64,
+            #[rbitpack(bits = 60)]
+            high: u64,
+            #[rbitpack(enum)]
+            mode: Mode,
+            last: bool,
+        }
+
+        let wide = Wide {
+            low: u64::MAX,
+            high: 0b1011,
+            mode: Mode::Sleep,
+            last: true,
+        };
+        let packed: u128 = wide.pack();
+        assert_eq!(packed, 0b110 << 124 | 0b1011 << 64 | u64::MAX as u128);
+        assert_eq!(<Wide as BitPack>::BITS, 127);
+        assert_eq!(Wide::LAST_MASK, 1 << 126);
+
+        let unpacked = Wide::unpack(packed);
+        assert_eq!(unpacked.low, u64::MAX);
+        assert_eq!(unpacked.high, 0b1011);
+        assert_eq!(unpacked.mode, Mode::Sleep);
+        assert_eq!(unpacked.last, true);
+        assert!(Wide::try_unpack(1 << 127).is_err());
+    }
+
+    #[test]
+    fn test_overflow_allowed_u128() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u128", overflow = true)]
+        struct TooWide {
+            #[rbitpack(bits = 64)]
+            a: u64,
+            #[rbitpack(bits = 64)]
+            b: u64,
+            c: bool,
+        }
+
+        let packed = TooWide {
+            a: 1,
+            b: 2,
+            c: true,
+        }
+        .pack();
+        assert_eq!(packed, 2 << 64 | 1);
+        let unpacked = TooWide::unpack(packed);
+        assert_eq!((unpacked.a, unpacked.b, unpacked.c), (1, 2, false));
+    }
+
+    #[test]
+    fn test_pack_unpack_signed_size() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "i8", packed_type = "SampleSigned")]
+        struct Sample {
+            #[rbitpack(bits = 7)]
+            level: u8,
+            negative: bool,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8")]
+        struct Unsigned {
+            #[rbitpack(bits = 7)]
+            level: u8,
+            negative: bool,
+        }
+
+        let sample = Sample {
+            level: 0b111_1110,
+            negative: true,
+        };
+        let packed: i8 = sample.pack();
+        assert_eq!(packed, -2);
+        let unsigned = Unsigned {
+            level: 0b111_1110,
+            negative: true,
+        };
+        assert_eq!(packed as u8, unsigned.pack());
+        assert_eq!(Sample::NEGATIVE_MASK, i8::MIN);
+        assert_eq!(Sample::ALL_MASK, -1);
+        assert_eq!(Sample::pack_bytes(&sample), [0xfe]);
+
+        let unpacked = Sample::unpack(-1);
+        assert_eq!(unpacked.level, 0b111_1111);
+        assert_eq!(unpacked.negative, true);
+        assert!(Sample::try_unpack(i8::MIN).is_ok());
+
+        let packed = SampleSigned::from(sample).with_negative(false);
+        assert_eq!(packed.0, 0b111_1110);
+        assert_eq!(packed.with_negative(true).0, -2);
+        assert_eq!(packed.level(), 0b111_1110);
+    }
+
+    #[test]
+    fn test_pack_unpack_bytes() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u32")]
+        struct Little {
+            #[rbitpack(bits = 12)]
+            a: u16,
+            b: bool,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u32", endian = "big")]
+        struct Big {
+            #[rbitpack(bits = 12)]
+            a: u16,
+            b: bool,
+        }
+
+        assert_eq!(Little::PACKED_BYTES, 2);
+        let bytes: [u8; 2] = Little { a: 0xabc, b: true }.pack_bytes();
+        assert_eq!(bytes, [0xbc, 0x1a]);
+        let unpacked = Little::unpack_bytes(bytes);
+        assert_eq!((unpacked.a, unpacked.b), (0xabc, true));
+
+        let bytes = Big { a: 0xabc, b: true }.pack_bytes();
+        assert_eq!(bytes, [0x1a, 0xbc]);
+        let unpacked = Big::unpack_bytes(bytes);
+        assert_eq!((unpacked.a, unpacked.b), (0xabc, true));
+        assert!(Big::try_unpack_bytes([0x20, 0]).is_err());
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u32", order = "msb0", endian = "big")]
+        struct Msb0 {
+            #[rbitpack(bits = 12)]
+            a: u16,
+            b: bool,
+        }
+
+        // In msb0 order the bits start from the most significant end.
+        let bytes = Msb0 { a: 0xabc, b: true }.pack_bytes();
+        assert_eq!(bytes, [0xab, 0xc8]);
+        let unpacked = Msb0::unpack_bytes(bytes);
+        assert_eq!((unpacked.a, unpacked.b), (0xabc, true));
+    }
+
+    #[test]
+    fn test_pack_unpack_bytes_auto() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto")]
+        struct Little {
+            #[rbitpack(bits = 64)]
+            a: u64,
+            #[rbitpack(bits = 12)]
+            b: u16,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto", endian = "big")]
+        struct Big {
+            #[rbitpack(bits = 64)]
+            a: u64,
+            #[rbitpack(bits = 12)]
+            b: u16,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto", order = "msb0", endian = "big")]
+        struct Msb0 {
+            #[rbitpack(bits = 64)]
+            a: u64,
+            #[rbitpack(bits = 12)]
+            b: u16,
+        }
+
+        let a = 0x0102_0304_0506_0708;
+        let bytes: [u8; 10] = Little { a, b: 0xabc }.pack_bytes();
+        assert_eq!(bytes, [8, 7, 6, 5, 4, 3, 2, 1, 0xbc, 0x0a]);
+        let unpacked = Little::unpack_bytes(bytes);
+        assert_eq!((unpacked.a, unpacked.b), (a, 0xabc));
+
+        let bytes = Big { a, b: 0xabc }.pack_bytes();
+        assert_eq!(bytes, [0x0a, 0xbc, 1, 2, 3, 4, 5, 6, 7, 8]);
+        let unpacked = Big::unpack_bytes(bytes);
+        assert_eq!((unpacked.a, unpacked.b), (a, 0xabc));
+
+        let bytes = Msb0 { a, b: 0xabc }.pack_bytes();
+        assert_eq!(bytes, [1, 2, 3, 4, 5, 6, 7, 8, 0xab, 0xc0]);
+        let unpacked = Msb0::unpack_bytes(bytes);
+        assert_eq!((unpacked.a, unpacked.b), (a, 0xabc));
+    }
+
+    #[test]
+    fn test_generic_structs() {
+        use std::marker::PhantomData;
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8")]
+        struct Flags<T> {
+            a: bool,
+            b: bool,
+            _marker: PhantomData<T>,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto")]
+        struct Borrowed<'a, T: Copy>
+        where
+            T: Default,
+        {
+            a: bool,
+            #[rbitpack(bits = 3)]
+            level: u8,
+            #[rbitpack(skip)]
+            value: T,
+            #[rbitpack(skip, default = "no_name")]
+            name: &'a str,
+        }
+
+        fn no_name() -> &'static str {
+            ""
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u16")]
+        struct Tagged<M: bitval::BitEnum> {
+            flag: bool,
+            #[rbitpack(enum)]
+            mode: M,
+            last: bool,
+        }
+
+        let flags = Flags::<String> {
+            a: false,
+            b: true,
+            _marker: PhantomData,
+        };
+        assert_eq!(flags.pack(), 0b10);
+        assert_eq!(flags.pack_bytes(), [0b10]);
+        let unpacked = Flags::<String>::unpack(0b01);
+        assert_eq!((unpacked.a, unpacked.b), (true, false));
+        assert_eq!(<Flags<u32> as BitPack>::BITS, 2);
+        assert_eq!(Flags::<u32>::B_MASK, 0b10);
+
+        let borrowed = Borrowed {
+            a: true,
+            level: 5,
+            value: 7u32,
+            name: "borrowed",
+        };
+        assert_eq!(borrowed.pack(), vec![0b1011]);
+        let unpacked = Borrowed::<u32>::unpack(vec![0b1011]);
+        assert_eq!((unpacked.a, unpacked.level), (true, 5));
+        assert_eq!((unpacked.value, unpacked.name), (0, ""));
+        assert_eq!(Borrowed::<u32>::unpack_bytes([0b1011]).level, 5);
+
+        let tagged = Tagged {
+            flag: true,
+            mode: Mode::Sleep,
+            last: true,
+        };
+        assert_eq!(tagged.pack(), 0b1101);
+        assert_eq!(Tagged::<Mode>::LAST_BIT, 3);
+        let unpacked = Tagged::<Mode>::unpack(0b0101);
+        assert_eq!(
+            (unpacked.flag, unpacked.mode, unpacked.last),
+            (true, Mode::Sleep, false)
+        );
+        assert!(matches!(
+            Tagged::<Mode>::try_unpack(0b1_0000),
+            Err(UnpackError::UnexpectedBit { bit: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn test_const_pack_unpack() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u16", packed_type = "ConfigPacked")]
+        struct Config {
+            enabled: bool,
+            #[rbitpack(bits = 4, out_of_range = "saturate")]
+            retries: u8,
+            #[rbitpack(bits = 3)]
+            level: u8,
+        }
+
+        const DEFAULT_CONFIG: u16 = Config {
+            enabled: true,
+            retries: 20,
+            level: 2,
+        }
+        .pack();
+        const UNPACKED: Config = Config::unpack(DEFAULT_CONFIG);
+        const CHECKED: bool = Config::try_unpack(1 << 15).is_err();
+        const PACKED: ConfigPacked = ConfigPacked(0).with_enabled(true).with_level(5);
+        const LEVEL: u8 = PACKED.level();
+        static BUFFER: [u8; Config::unpack(0b1010_0000).level as usize] = [0; 5];
+
+        assert_eq!(DEFAULT_CONFIG, 0b0101_1111);
+        assert_eq!(
+            (UNPACKED.enabled, UNPACKED.retries, UNPACKED.level),
+            (true, 15, 2)
+        );
+        assert_eq!(CHECKED, true);
+        assert_eq!(PACKED.0, 0b1010_0001);
+        assert_eq!(LEVEL, 5);
+        assert_eq!(BUFFER.len(), 5);
+    }
+
+    #[test]
+    fn test_reserved_bits() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8")]
+        struct Ignored {
+            ready: bool,
+            #[rbitpack(reserved = 3)]
+            _reserved: (),
+            #[rbitpack(bits = 2)]
+            mode: u8,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8", reserved = "zero")]
+        struct Zero {
+            ready: bool,
+            #[rbitpack(reserved = 3)]
+            _reserved: (),
+            #[rbitpack(bits = 2)]
+            mode: u8,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto", reserved = "preserve")]
+        struct Preserved {
+            ready: bool,
+            #[rbitpack(reserved = 3)]
+            reserved: u8,
+            #[rbitpack(bits = 2)]
+            mode: u8,
+        }
+
+        let ignored = Ignored {
+            ready: true,
+            _reserved: (),
+            mode: 3,
+        };
+        assert_eq!(ignored.pack(), 0b0011_0001);
+        assert_eq!(Ignored::MODE_BIT, 4);
+        assert_eq!(Ignored::_RESERVED_MASK, 0b1110);
+        let unpacked = Ignored::unpack(0b0011_1111);
+        assert_eq!((unpacked.ready, unpacked.mode), (true, 3));
+        assert!(Ignored::try_unpack(0b1110).is_ok());
+
+        assert_eq!(Zero::unpack(0b0010_0001).mode, 2);
+        assert_eq!(
+            Zero::try_unpack(0b0010_1001).err(),
+            Some(UnpackError::InvalidValue {
+                struct_name: "Zero",
+                field: "_reserved",
+                bit: 1,
+                value: 0b100,
+            })
+        );
+
+        let preserved = Preserved {
+            ready: false,
+            reserved: 0b1111,
+            mode: 1,
+        };
+        assert_eq!(preserved.pack(), vec![0b0001_1110]);
+        let unpacked = Preserved::unpack(vec![0b0010_1011]);
+        assert_eq!(
+            (unpacked.ready, unpacked.reserved, unpacked.mode),
+            (true, 0b101, 2)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Reserved bits of field '_reserved' in struct 'Control' are set.")]
+    fn test_unpack_set_reserved_bits() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8", reserved = "zero")]
+        struct Control {
+            ready: bool,
+            #[rbitpack(reserved = 3)]
+            _reserved: (),
+        }
+
+        Control::unpack(0b0100);
+    }
+
+    #[test]
+    fn test_unpack_strict() {
+        #[derive(BitwisePackable, Debug)]
+        #[rbitpack(size = "u16")]
+        struct Register {
+            #[rbitpack(bits = 0..2)]
+            channel: u8,
+            #[rbitpack(bits = 4..=7)]
+            prescaler: u8,
+        }
+
+        #[derive(BitwisePackable, Debug)]
+        #[rbitpack(size = "u8", order = "msb0")]
+        struct Msb0 {
+            a: bool,
+            #[rbitpack(bit = 3)]
+            b: bool,
+        }
+
+        #[derive(BitwisePackable, Debug)]
+        #[rbitpack(size = "auto")]
+        struct Wide {
+            #[rbitpack(bits = 60)]
+            low: u64,
+            #[rbitpack(bits = 64..68)]
+            high: u8,
+        }
+
+        let register = Register::unpack_strict(0b1001_0010).unwrap();
+        assert_eq!((register.channel, register.prescaler), (2, 9));
+        // Bit 2 sits between the two fields, which `try_unpack` does not check.
+        assert!(Register::try_unpack(0b0100).is_ok());
+        assert_eq!(
+            Register::unpack_strict(0b0100).unwrap_err(),
+            UnpackError::UnexpectedBit {
+                struct_name: "Register",
+                bit: 2,
+            }
+        );
+        assert_eq!(
+            <Register as BitPack>::unpack_strict(1 << 12).unwrap_err(),
+            UnpackError::UnexpectedBit {
+                struct_name: "Register",
+                bit: 12,
+            }
+        );
+
+        assert!(Msb0::unpack_strict(0b1001_0000).is_ok());
+        assert_eq!(
+            Msb0::unpack_strict(0b0010_0000).unwrap_err(),
+            UnpackError::UnexpectedBit {
+                struct_name: "Msb0",
+                bit: 2,
+            }
+        );
+
+        let wide = Wide::unpack_strict(vec![1, 0b1010]).unwrap();
+        assert_eq!((wide.low, wide.high), (1, 0b1010));
+        assert_eq!(
+            Wide::unpack_strict(vec![1, 0, 0]).unwrap_err(),
+            UnpackError::LengthMismatch {
+                struct_name: "Wide",
+                expected: 2,
+                found: 3,
+            }
+        );
+        assert_eq!(
+            Wide::unpack_strict(vec![1 << 62, 0]).unwrap_err(),
+            UnpackError::UnexpectedBit {
+                struct_name: "Wide",
+                bit: 62,
+            }
+        );
+    }
+
+    #[test]
+    fn test_conversions() {
+        use bitval::{BitOrder, Bitfield};
+
+        #[derive(BitwisePackable, Debug)]
+        #[rbitpack(size = "u16")]
+        struct Header {
+            ack: bool,
+            #[rbitpack(bits = 3)]
+            retries: u8,
+        }
+
+        #[derive(BitwisePackable, Debug)]
+        #[rbitpack(size = "u8", strict)]
+        struct Strict {
+            a: bool,
+            #[rbitpack(bit = 2)]
+            b: bool,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto", order = "msb0")]
+        struct Auto {
+            a: bool,
+            #[rbitpack(bits = 4)]
+            b: u8,
+        }
+
+        fn send(packed: impl Into<u16>) -> u16 {
+            packed.into()
+        }
+
+        let header = Header {
+            ack: true,
+            retries: 5,
+        };
+        assert_eq!(send(&header), 0b1011);
+        assert_eq!(u16::from(header), 0b1011);
+        let header: Header = 0b0111.into();
+        assert_eq!((header.ack, header.retries), (true, 3));
+
+        fn parse(packed: u8) -> Result<Strict, UnpackError> {
+            let strict = Strict::try_from(packed)?;
+            Ok(strict)
+        }
+
+        let strict = parse(0b101).unwrap();
+        assert_eq!((strict.a, strict.b), (true, true));
+        assert_eq!(u8::from(&strict), 0b101);
+        assert!(matches!(
+            parse(0b010),
+            Err(UnpackError::UnexpectedBit { bit: 1, .. })
+        ));
+
+        let auto = Auto { a: true, b: 0b1001 };
+        let parts: Vec<u64> = (&auto).into();
+        assert_eq!(parts, vec![0b1_1001 << 59]);
+        let bitfield = Bitfield::from(auto);
+        assert_eq!(bitfield.order(), BitOrder::Msb0);
+        assert_eq!(bitfield.get_bits(1, 4), 0b1001);
+        let auto = Auto::try_from(bitfield.parts).unwrap();
+        assert_eq!((auto.a, auto.b), (true, 0b1001));
+        assert!(matches!(
+            Auto::try_from(vec![]),
+            Err(UnpackError::TooShort { .. })
+        ));
+
+        #[derive(BitwisePackable, Debug)]
+        #[rbitpack(size = "u8")]
+        struct Status {
+            #[rbitpack(enum)]
+            mode: Mode,
+        }
+
+        let status = Status::try_from(0b10).unwrap();
+        assert_eq!(status.mode, Mode::Sleep);
+        assert!(matches!(
+            Status::try_from(0b11),
+            Err(UnpackError::InvalidValue {
+                bit: 0,
+                value: 3,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_serde() {
+        use std::marker::PhantomData;
+
+        #[derive(BitwisePackable, Debug, PartialEq)]
+        #[rbitpack(size = "u8", serde = "packed")]
+        struct Packed {
+            a: bool,
+            #[rbitpack(bits = 3)]
+            b: u8,
+        }
+
+        #[derive(BitwisePackable, Debug, PartialEq)]
+        #[rbitpack(size = "auto", serde = "packed")]
+        struct Auto {
+            a: bool,
+            b: bool,
+        }
+
+        #[derive(BitwisePackable, Debug, PartialEq)]
+        #[rbitpack(size = "u8", serde = "names")]
+        struct Names<T> {
+            read: bool,
+            write: bool,
+            r#exec: bool,
+            marker: PhantomData<T>,
+        }
+
+        let packed = Packed { a: true, b: 5 };
+        assert_eq!(serde_json::to_string(&packed).unwrap(), "11");
+        assert_eq!(serde_json::from_str::<Packed>("11").unwrap(), packed);
+        let error = serde_json::from_str::<Packed>("16").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "bit 4 is set outside the fields of struct 'Packed'"
+        );
+
+        let auto = Auto { a: false, b: true };
+        assert_eq!(serde_json::to_string(&auto).unwrap(), "[2]");
+        assert_eq!(serde_json::from_str::<Auto>("[2]").unwrap(), auto);
+
+        let names = Names::<u32> {
+            read: true,
+            write: false,
+            exec: true,
+            marker: PhantomData,
+        };
+        let json = serde_json::to_string(&names).unwrap();
+        assert_eq!(json, r#"["read","exec"]"#);
+        assert_eq!(serde_json::from_str::<Names<u32>>(&json).unwrap(), names);
+        assert_eq!(serde_json::from_str::<Names<u32>>("[]").unwrap().pack(), 0);
+        let error = serde_json::from_str::<Names<u32>>(r#"["delete"]"#).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("unknown variant `delete`, expected one of `read`, `write`, `exec`"));
+    }
+
+    #[test]
+    fn test_debug_packed() {
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u16", packed_type = "ExamplePacked")]
+        struct Example {
+            a: bool,
+            b: bool,
+            c: bool,
+            #[rbitpack(bits = 8..12)]
+            retries: u8,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8", order = "msb0")]
+        struct Msb {
+            a: bool,
+            b: bool,
+        }
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "auto")]
+        struct Auto {
+            a: bool,
+            #[rbitpack(bits = 4)]
+            b: u8,
+        }
+
+        let packed = Example {
+            a: true,
+            b: false,
+            c: true,
+            retries: 0,
+        }
+        .pack();
+        assert_eq!(
+            format!("{:?}", Example::debug_packed(packed)),
+            "Example { a: true, c: true }"
+        );
+        assert_eq!(
+            format!("{:?}", Example::debug_packed(0b0001_0110_0001_0010)),
+            "Example { b: true, retries: 6, stray_bits: [4, 12] }"
+        );
+        assert_eq!(
+            format!("{:?}", ExamplePacked(0b0101)),
+            "Example { a: true, c: true }"
+        );
+
+        assert_eq!(
+            format!("{:?}", Msb::debug_packed(0b0100_0001)),
+            "Msb { b: true, stray_bits: [7] }"
+        );
+
+        let packed = Auto { a: false, b: 9 }.pack();
+        assert_eq!(
+            format!("{:?}", Auto::debug_packed(&packed)),
+            "Auto { b: 9 }"
+        );
+        assert_eq!(
+            format!("{:?}", Auto::debug_packed(&[1 << 5 | 1, 1])),
+            "Auto { a: true, stray_bits: [5, 64] }"
+        );
+        assert_eq!(format!("{:?}", Auto::debug_packed(&[])), "Auto");
+
+        #[derive(BitwisePackable)]
+        #[rbitpack(size = "u8", reserved = "zero")]
+        struct Raw {
+            r#type: bool,
+            #[rbitpack(reserved = 1)]
+            r#pad: (),
+        }
+
+        assert_eq!(
+            format!("{:?}", Raw::debug_packed(0b01)),
+            "Raw { type: true }"
+        );
+        assert!(matches!(
+            Raw::try_unpack(0b10),
+            Err(UnpackError::InvalidValue { field: "pad", .. })
+        ));
+    }
 }